@@ -2,7 +2,6 @@
 //
 // If you are using the `rustc-serialize` feature, use `RustcDecodable` and `RustcEncodable`
 // instead of `Deserialize` and `Serialize`, respectively.
-#![feature(proc_macro)]
 
 #[macro_use] extern crate anterofit;
 
@@ -35,6 +34,8 @@ struct NewPost<'a> {
 }
 
 #[service]
+// Attributes on the trait add steps to every request it makes.
+#[header("Accept", "application/json")]
 trait PostService {
     /// Get a Post by id.
     fn get_post(&self, id: u64) -> Post {
@@ -51,7 +52,11 @@ trait PostService {
         GET("/user/{}/posts", userid)
     }
 
-    // TODO: demonstrate `query!{}`
+    /// Get a page of posts by a specific user.
+    // The verb and URL can also be given as an attribute, with the parameters annotated
+    // to say where they go in the request. No method body is required.
+    #[get("/user/{userid}/posts")]
+    fn posts_page(&self, #[path] userid: u64, #[query("_page")] page: u32) -> Vec<Post>;
 
     /// Create a new Post under the given user ID with the given title and body.
     fn new_post(&self, userid: u64, title: &str, body: &str) -> Post {
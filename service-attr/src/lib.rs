@@ -1,323 +1,672 @@
-#![feature(proc_macro)]
-
-extern crate syn;
-#[macro_use] extern crate quote;
 extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use] extern crate quote;
+extern crate syn;
 
 use proc_macro::TokenStream;
-use quote::{Tokens, ToTokens};
+use proc_macro2::TokenStream as TokenStream2;
+use quote::ToTokens;
+use syn::parse::{Parse, ParseStream};
+use syn::spanned::Spanned;
 use syn::*;
 
-use std::iter::Peekable;
-
+/// Generate a service trait and its implementation for adapters.
+///
+/// Method bodies use the same statement syntax as `service!{}`. Alternatively, the HTTP verb
+/// and URL can be given as an attribute on the method, with its parameters annotated
+/// to say how they are added to the request; the method body is then optional,
+/// and any statements in it are applied afterwards.
+///
+/// * `#[get("/users/{id}")]`, `#[post(..)]`, `#[put(..)]`, `#[patch(..)]`, `#[delete(..)]`:
+///   set the verb and URL. Placeholders in braces are replaced by the `#[path]` parameter
+///   of the same name.
+/// * `#[path]` or `#[path("name")]`: fill the URL placeholder with the parameter's name,
+///   or the given name. The value is percent-encoded as a single path segment.
+/// * `#[query]` or `#[query("name")]`: add the parameter to the query string.
+/// * `#[header("Name")]`: set the given header to the parameter.
+/// * `#[body]`: set the parameter as the request body. If its type is a reference,
+///   it will be serialized immediately, as with `body!(EAGER: ..)`.
+///
+/// Placeholders without a matching `#[path]` parameter, and vice versa, are compile errors.
+///
+/// ```rust,ignore
+/// #[service]
+/// trait UserService {
+///     #[get("/users/{id}")]
+///     fn get_user(&self, #[path] id: u64, #[query("fields")] fields: &str) -> User;
+/// }
+/// ```
+///
+/// Steps which apply to every method of the trait can be declared with attributes on the trait;
+/// these are applied before the steps of each method:
+///
+/// * `#[prefix("/api/v2")]`: prepend a path to the URL of every request.
+/// * `#[header("Name", value)]`: set a header on every request.
+/// * `#[query("key", value)]`: add a query pair to every request.
+///
+/// ```rust,ignore
+/// #[service]
+/// #[prefix("/api/v3")]
+/// #[header("Accept", "application/vnd.github.v3+json")]
+/// trait RepoService {
+///     #[get("/users/{user}/repos")]
+///     fn repos(&self, #[path] user: &str) -> Vec<Repo>;
+/// }
+/// ```
+///
+/// The trait may have generics, supertraits and a `where` clause. These are added to the generated
+/// impls, so `delegate!()` invocations only need to declare their own generics:
+///
+/// ```rust,ignore
+/// #[service]
+/// trait PageService<T>: UserService where T: Deserialize {
+///     #[get("/{resource}")]
+///     fn page(&self, #[path] resource: &str, #[query] page: u32) -> Vec<T>;
+///
+///     delegate!(impl<A: AsRef<JsonAdapter>> for A { self.as_ref() });
+/// }
+/// ```
 #[proc_macro_attribute]
 pub fn service(args: TokenStream, input: TokenStream) -> TokenStream {
-    let item = parse_item(&input.to_string())
-        .expect("Input required to contain a trait and zero or more `delegate!()` invocations");
+    let args = TokenStream2::from(args);
+
+    if !args.is_empty() {
+        return Error::new_spanned(args, "#[service] attribute does not take arguments")
+            .to_compile_error().into();
+    }
 
-    let service_trait = ServiceTrait::from_item(item);
+    let item = parse_macro_input!(input as ItemTrait);
+
+    match ServiceTrait::from_item(item) {
+        Ok(service_trait) => service_trait.output().into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
 
-    assert!(args.to_string().is_empty(), "#[service] attribute does not take arguments");
+/// Combine a series of results, keeping every error so they can all be reported at once.
+fn collect_results<T, I: IntoIterator<Item = Result<T>>>(results: I) -> Result<Vec<T>> {
+    let mut values = vec![];
+    let mut error: Option<Error> = None;
+
+    for res in results {
+        match res {
+            Ok(val) => values.push(val),
+            Err(e) => match error {
+                Some(ref mut error) => error.combine(e),
+                None => error = Some(e),
+            },
+        }
+    }
 
-    service_trait.output().parse().expect("Failed to parse output")
+    match error {
+        Some(e) => Err(e),
+        None => Ok(values),
+    }
 }
 
 struct ServiceTrait {
-    name: Ident,
-    vis: Visibility,
-    attrs: Vec<Attribute>,
+    item: ItemTrait,
+    defaults: Vec<TokenStream2>,
     methods: Vec<ServiceMethod>,
     delegates: Vec<Delegate>,
 }
 
-fn assert_generics_empty(generics: &Generics) {
-    assert!(
-        generics.lifetimes.is_empty() &&
-        generics.ty_params.is_empty() &&
-        generics.where_clause.predicates.is_empty(),
-        "Generics are (currently) not supported on service traits"
-    )
-}
-
 impl ServiceTrait {
-    fn from_item(item: Item) -> Self {
-        let items = if let ItemKind::Trait(unsafety, generics, bounds, items) = item.node {
-            assert_eq!(unsafety, Unsafety::Normal, "Unsafe traits are not supported");
-            assert_generics_empty(&generics);
-            assert!(bounds.is_empty(), "Bounds are not supported on service traits");
-            items
-        } else {
-            panic!("Target of `#[service]` attribute must be a trait");
-        };
+    fn from_item(mut item: ItemTrait) -> Result<Self> {
+        if let Some(unsafety) = item.unsafety {
+            return Err(Error::new_spanned(unsafety, "Unsafe traits are not supported"));
+        }
 
-        let (methods, delegates) = collect_items(items);
+        let defaults = take_defaults(&mut item.attrs)?;
+        let (methods, delegates) = collect_items(item.items.drain(..).collect())?;
 
-        ServiceTrait {
-            name: item.ident,
-            vis: item.vis,
-            attrs: item.attrs,
+        Ok(ServiceTrait {
+            item: item,
+            defaults: defaults,
             methods: methods,
             delegates: delegates,
-        }
+        })
     }
 
-    fn output(&self) -> Tokens {
-        let vis = &self.vis;
-        let name = &self.name;
-        let attrs = &self.attrs;
+    fn output(&self) -> TokenStream2 {
+        let ItemTrait { ref attrs, ref vis, ref ident, ref generics, ref colon_token,
+                        ref supertraits, .. } = self.item;
+        let where_clause = &generics.where_clause;
+        let decls = self.methods.iter().map(ServiceMethod::decl);
 
         let mut out = quote! {
             #(#attrs)*
-            #vis trait #name
+            #vis trait #ident #generics #colon_token #supertraits #where_clause {
+                #(#decls)*
+            }
         };
 
-        out.append("{");
-
-        for method in &self.methods {
-            method.decl(&mut out);
-        }
-
-        out.append("}");
-
         if !self.delegates.is_empty() {
             for delegate in &self.delegates {
-                delegate.output(&self.name, &self.methods, &mut out);
+                out.extend(delegate.output(self));
             }
         } else {
-            let self_ = parse_token_trees("self").unwrap();
-
-            out.append("impl<T: ::anterofit::AbsAdapter> ");
-            self.name.to_tokens(&mut out);
-            out.append(" for T { ");
-
-            for method in &self.methods {
-                method.method_impl(&self_, &mut out);
-            }
-
-            out.append(" } ");
+            out.extend(Delegate::default_impl().output(self));
         }
 
         out
     }
 }
 
-fn collect_items(items: Vec<TraitItem>) -> (Vec<ServiceMethod>, Vec<Delegate>) {
+/// Remove the default step attributes from the trait, converting them to request steps.
+fn take_defaults(attrs: &mut Vec<Attribute>) -> Result<Vec<TokenStream2>> {
+    let mut results = vec![];
+
+    attrs.retain(|attr| {
+        let step = if attr.path().is_ident("prefix") {
+            attr.parse_args::<LitStr>().map(|prefix| quote!(prefix!(#prefix)))
+        } else if attr.path().is_ident("header") {
+            parse_default_pair(attr).map(|(name, val)| quote!(headers! { #name => #val }))
+        } else if attr.path().is_ident("query") {
+            parse_default_pair(attr).map(|(key, val)| quote!(query! { #key => #val }))
+        } else {
+            return true;
+        };
+
+        results.push(step);
+        false
+    });
+
+    collect_results(results)
+}
+
+/// Parse `#[header("Name", value)]` or `#[query("key", value)]`.
+fn parse_default_pair(attr: &Attribute) -> Result<(LitStr, Expr)> {
+    attr.parse_args_with(|input: ParseStream| {
+        let name = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let val = input.parse()?;
+        Ok((name, val))
+    }).map_err(|e| Error::new(e.span(), format!("{} (expected e.g. `#[{}(\"name\", value)]`)",
+                                                 e, attr.path().get_ident().unwrap())))
+}
+
+fn collect_items(items: Vec<TraitItem>) -> Result<(Vec<ServiceMethod>, Vec<Delegate>)> {
     let mut methods = vec![];
     let mut delegates = vec![];
 
-    for item in items {
-        match item.node {
-            TraitItemKind::Method(..) => methods.push(ServiceMethod::from_trait_item(item)),
-            TraitItemKind::Macro(mac) => delegates.push(Delegate::from_mac(mac)),
-            _ => panic!("Unsupported item in service trait: {:?}", item),
+    let results = items.into_iter().map(|item| {
+        match item {
+            TraitItem::Fn(method) => methods.push(ServiceMethod::from_trait_item(method)?),
+            TraitItem::Macro(mac) => delegates.push(Delegate::from_mac(mac)?),
+            other => return Err(Error::new_spanned(other, "Unsupported item in service trait \
+                                                          (only methods and `delegate!()` are allowed)")),
         }
-    }
 
-    (methods, delegates)
+        Ok(())
+    }).collect::<Vec<_>>();
+
+    collect_results(results)?;
+
+    Ok((methods, delegates))
 }
 
 struct ServiceMethod {
-    name: Ident,
     attrs: Vec<Attribute>,
-    sig: MethodSig,
+    sig: Signature,
+    verb: Option<Verb>,
     body: Vec<Stmt>,
 }
 
 impl ServiceMethod {
-    fn from_trait_item(trait_item: TraitItem) -> Self {
-        let (sig, block) = if let TraitItemKind::Method(sig, block) = trait_item.node {
-            let block = block.expect("Every trait method must have a block.");
+    fn from_trait_item(method: TraitItemFn) -> Result<Self> {
+        let TraitItemFn { attrs, mut sig, default, .. } = method;
 
-            (sig, block)
-        } else {
-            panic!("Unsupported item in service trait (only methods are allowed): {:?}", trait_item)
+        let params = take_param_attrs(&mut sig)?;
+
+        let mut verb_attrs = vec![];
+        let mut other_attrs = vec![];
+
+        for attr in attrs {
+            if VERBS.iter().any(|verb| attr.path().is_ident(verb)) {
+                verb_attrs.push(attr);
+            } else {
+                other_attrs.push(attr);
+            }
+        }
+
+        if verb_attrs.len() > 1 {
+            return Err(Error::new_spanned(&verb_attrs[1], "Method has more than one HTTP verb attribute"));
+        }
+
+        let verb = match verb_attrs.first() {
+            Some(attr) => Some(Verb::new(attr, params, &sig)?),
+            None => {
+                if let Some((attr, _)) = params.first() {
+                    return Err(Error::new_spanned(attr, "Parameter attributes require an HTTP verb \
+                                                         attribute on the method, e.g. `#[get(\"/url\")]`"));
+                }
+
+                None
+            },
         };
 
-        ServiceMethod {
-            name: trait_item.ident,
-            attrs: trait_item.attrs,
+        let body = match default {
+            Some(block) => block.stmts,
+            None if verb.is_some() => vec![],
+            None => return Err(Error::new_spanned(&sig, "Service method must have a body or an \
+                                                         HTTP verb attribute, e.g. `#[get(\"/url\")]`")),
+        };
+
+        Ok(ServiceMethod {
+            attrs: other_attrs,
             sig: sig,
-            body: block.stmts
-        }
+            verb: verb,
+            body: body,
+        })
     }
 
-    fn header(&self, out: &mut Tokens) {
-        out.append_all(&self.attrs);
-        out.append("fn");
-        self.name.to_tokens(out);
-        self.sig.generics.to_tokens(out);
-        out.append("(");
-        out.append_separated(&self.sig.decl.inputs, ",");
-        out.append(")");
-
-        out.append("-> anterofit::Request");
-
-        if let FunctionRetTy::Ty(ref ret_ty) = self.sig.decl.output {
-            out.append("<");
-            ret_ty.to_tokens(out);
-            out.append(">");
+    fn header(&self, decl: bool) -> TokenStream2 {
+        let attrs = &self.attrs;
+        let name = &self.sig.ident;
+        let generics = &self.sig.generics;
+        let where_clause = &generics.where_clause;
+
+        let inputs = self.sig.inputs.iter().map(|arg| {
+            let mut arg = arg.clone();
+
+            // Patterns like `mut x` aren't allowed in declarations
+            if let FnArg::Typed(PatType { ref mut pat, .. }) = arg {
+                if let Pat::Ident(ref mut pat_ident) = **pat {
+                    if decl { pat_ident.mutability = None; }
+                }
+            }
+
+            arg
+        });
+
+        let ret = match self.sig.output {
+            ReturnType::Type(_, ref ty) => quote!(<#ty>),
+            ReturnType::Default => quote!(),
+        };
+
+        quote! {
+            #(#attrs)*
+            fn #name #generics (#(#inputs),*) -> ::anterofit::Request #ret #where_clause
         }
     }
 
-    fn decl(&self, out: &mut Tokens) {
-        self.header(out);
-        out.append(";");
+    fn decl(&self) -> TokenStream2 {
+        let header = self.header(true);
+        quote!(#header;)
     }
 
-    fn method_impl(&self, get_adpt: &[TokenTree], out: &mut Tokens) {
-        self.header(out);
-        out.append("{ request_impl! { ");
-        out.append_all(get_adpt);
-        out.append(";");
-        out.append_all(&self.body);
-        out.append(" } } ");
+    fn method_impl(&self, get_adpt: &TokenStream2, defaults: &[TokenStream2]) -> TokenStream2 {
+        let header = self.header(false);
+        let body = &self.body;
+
+        // Parameters substituted into the URL are percent-encoded by `request_impl!{}`
+        let params = self.sig.inputs.iter().filter_map(|arg| match *arg {
+            FnArg::Typed(PatType { ref pat, .. }) => match **pat {
+                Pat::Ident(ref pat_ident) => Some(&pat_ident.ident),
+                _ => None,
+            },
+            FnArg::Receiver(_) => None,
+        });
+
+        let steps = match self.verb {
+            Some(ref verb) if body.is_empty() => quote!(#verb),
+            Some(ref verb) => quote!(#verb; #(#body)*),
+            None => quote!(#(#body)*),
+        };
+
+        quote! {
+            #header {
+                request_impl! { #get_adpt; [#(#params)*] [#(#defaults);*] #steps }
+            }
+        }
     }
 }
 
-struct Delegate {
-    generics: Vec<TokenTree>,
-    for_type: Vec<TokenTree>,
-    where_clause: Vec<TokenTree>,
-    get_adpt: Vec<TokenTree>,
+const VERBS: &'static [&'static str] = &["get", "post", "put", "patch", "delete"];
+
+#[derive(Copy, Clone)]
+enum ParamKind {
+    Path,
+    Query,
+    Header,
+    Body,
 }
 
-impl Delegate {
-    fn from_mac(mac: Mac) -> Self {
-        assert_eq!(mac.path, "delegate".into(), "Only `delegate!()` macro invocations are allowed \
-                                                 inside service traits.");
-        Self::parse(mac.tts)
-    }
+/// An attribute on a method parameter, e.g. `#[query("page")] page: u32`.
+struct ParamAttr {
+    kind: ParamKind,
+    name: Option<LitStr>,
+    param: Ident,
+    eager: bool,
+}
 
-    fn parse(mut tokens: Vec<TokenTree>) -> Self {
-        let tokens = match tokens.pop() {
-            Some(TokenTree::Delimited(delimited)) => delimited.tts,
-            None => panic!("Empty `delegate!()` invocation!"),
-            Some(token) =>  {
-                tokens.push(token);
-                panic!("Unsupported `delegate!()` invocation: {:?}", tokens)
-            },
+/// Remove the parameter attributes from `sig`, returning them along with the attributes themselves
+/// for error reporting.
+fn take_param_attrs(sig: &mut Signature) -> Result<Vec<(Attribute, ParamAttr)>> {
+    let mut results = vec![];
+
+    for arg in sig.inputs.iter_mut() {
+        let PatType { ref mut attrs, ref pat, ref ty, .. } = match *arg {
+            FnArg::Typed(ref mut pat_type) => pat_type,
+            FnArg::Receiver(_) => continue,
         };
 
-        let mut parser = DelegateParser(tokens.into_iter().peekable());
+        for attr in attrs.drain(..) {
+            results.push(parse_param_attr(attr, pat, ty));
+        }
+    }
 
-        parser.expect_keyword("impl");
+    collect_results(results)
+}
 
-        let generics = parser.get_generics();
+fn parse_param_attr(attr: Attribute, pat: &Pat, ty: &Type) -> Result<(Attribute, ParamAttr)> {
+    let kind = if attr.path().is_ident("path") {
+        ParamKind::Path
+    } else if attr.path().is_ident("query") {
+        ParamKind::Query
+    } else if attr.path().is_ident("header") {
+        ParamKind::Header
+    } else if attr.path().is_ident("body") {
+        ParamKind::Body
+    } else {
+        return Err(Error::new_spanned(attr.path(), "Unsupported parameter attribute \
+                                                    (expected `path`, `query`, `header` or `body`)"));
+    };
+
+    let name = match attr.meta {
+        Meta::Path(_) => None,
+        Meta::List(_) => Some(attr.parse_args::<LitStr>()?),
+        Meta::NameValue(_) => return Err(Error::new_spanned(&attr, "Expected `(\"name\")` in parameter attribute")),
+    };
+
+    if let (ParamKind::Header, None) = (kind, &name) {
+        return Err(Error::new_spanned(&attr, "`#[header]` requires a name, e.g. `#[header(\"X-Name\")]`"));
+    }
 
-        parser.expect_keyword("for");
+    let param = match *pat {
+        Pat::Ident(ref pat_ident) => pat_ident.ident.clone(),
+        _ => return Err(Error::new_spanned(pat, "Annotated parameters must be identifiers")),
+    };
 
-        let for_type = parser.get_type();
+    let eager = matches!(*ty, Type::Reference(_));
 
-        assert!(!for_type.is_empty(), "Expected type, got nothing");
+    Ok((attr, ParamAttr { kind: kind, name: name, param: param, eager: eager }))
+}
 
-        let where_clause = parser.get_where();
+/// The request described by a method's verb attribute and parameter attributes.
+struct Verb {
+    method: Ident,
+    url: LitStr,
+    path: Vec<(Ident, Ident)>,
+    query: Vec<(LitStr, Ident)>,
+    headers: Vec<(LitStr, Ident)>,
+    body: Option<(Ident, bool)>,
+}
 
-        let get_adpt = parser.get_body_inner();
+impl Verb {
+    fn new(attr: &Attribute, params: Vec<(Attribute, ParamAttr)>, sig: &Signature) -> Result<Self> {
+        let method = attr.path().get_ident().expect("verb attribute path was not an ident");
+        let url = attr.parse_args::<LitStr>().map_err(|e| {
+            Error::new(e.span(), format!("Expected URL string in attribute, e.g. `#[{}(\"/url\")]`", method))
+        })?;
+
+        let placeholders = url_placeholders(&url.value())
+            .map_err(|e| Error::new_spanned(&url, e))?;
+
+        let mut verb = Verb {
+            method: Ident::new(&method.to_string().to_uppercase(), method.span()),
+            url: url.clone(),
+            path: vec![],
+            query: vec![],
+            headers: vec![],
+            body: None,
+        };
 
-        Delegate {
-            generics: generics,
-            for_type: for_type,
-            where_clause: where_clause,
-            get_adpt: get_adpt,
+        let mut errors: Vec<Result<()>> = vec![];
+
+        for (attr, param) in params {
+            let key = param.name.as_ref().map_or_else(|| param.param.to_string(), LitStr::value);
+
+            match param.kind {
+                ParamKind::Path => {
+                    if !placeholders.contains(&key) {
+                        errors.push(Err(Error::new_spanned(&attr, format!(
+                            "`#[path]` parameter `{}` has no matching placeholder `{{{}}}` in URL {:?}",
+                            param.param, key, url.value()
+                        ))));
+                        continue;
+                    }
+
+                    let span = param.name.as_ref().map_or_else(|| param.param.span(), LitStr::span);
+                    verb.path.push((Ident::new(&key, span), param.param));
+                },
+                ParamKind::Query => verb.query.push((LitStr::new(&key, attr.span()), param.param)),
+                ParamKind::Header => verb.headers.push((LitStr::new(&key, attr.span()), param.param)),
+                ParamKind::Body => {
+                    if verb.body.is_some() {
+                        errors.push(Err(Error::new_spanned(&attr, "Method has more than one `#[body]` parameter")));
+                        continue;
+                    }
+
+                    verb.body = Some((param.param, param.eager));
+                },
+            }
         }
+
+        for placeholder in &placeholders {
+            if !verb.path.iter().any(|(key, _)| key == placeholder) {
+                errors.push(Err(Error::new_spanned(&url, format!(
+                    "placeholder `{{{}}}` has no matching `#[path]` parameter in `{}()`",
+                    placeholder, sig.ident
+                ))));
+            }
+        }
+
+        collect_results(errors)?;
+
+        Ok(verb)
     }
+}
+
+impl ToTokens for Verb {
+    fn to_tokens(&self, out: &mut TokenStream2) {
+        let Verb { ref method, ref url, .. } = *self;
+        let path = self.path.iter().map(|(key, param)| quote!(, #key = #param));
 
-    fn output(&self, trait_name: &Ident, methods: &[ServiceMethod], out: &mut Tokens) {
-        out.append("impl");
-        out.append_all(&self.generics);
-        trait_name.to_tokens(out);
-        out.append("for");
-        out.append_all(&self.for_type);
-        out.append_all(&self.where_clause);
-        out.append("{");
-
-        for method in methods {
-            method.method_impl(&self.get_adpt, out);
+        out.extend(quote!(#method(#url #(#path)*)));
+
+        if !self.query.is_empty() {
+            let (keys, params): (Vec<_>, Vec<_>) = self.query.iter().cloned().unzip();
+            out.extend(quote!(; query! { #(#keys => #params),* }));
         }
 
-        out.append("}");
+        if !self.headers.is_empty() {
+            let (names, params): (Vec<_>, Vec<_>) = self.headers.iter().cloned().unzip();
+            out.extend(quote!(; headers! { #(#names => #params),* }));
+        }
+
+        match self.body {
+            Some((ref param, true)) => out.extend(quote!(; body!(EAGER: #param))),
+            Some((ref param, false)) => out.extend(quote!(; body!(#param))),
+            None => (),
+        }
     }
 }
 
-struct DelegateParser<I: Iterator>(Peekable<I>);
+/// Get the names of the `{placeholders}` in a URL.
+fn url_placeholders(url: &str) -> ::std::result::Result<Vec<String>, String> {
+    let mut placeholders = vec![];
+    let mut chars = url.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            // `{{` is an escaped brace
+            '{' if chars.peek() == Some(&'{') => { chars.next(); },
+            '{' => {
+                let mut name = String::new();
+
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(ch) => name.push(ch),
+                        None => return Err(format!("unclosed placeholder `{{{}` in URL", name)),
+                    }
+                }
+
+                let valid = name.chars().next().is_some_and(|ch| ch.is_alphabetic() || ch == '_')
+                    && name.chars().all(|ch| ch.is_alphanumeric() || ch == '_');
+
+                if !valid {
+                    return Err(format!("placeholder `{{{}}}` must be a name, e.g. `{{id}}`", name));
+                }
+
+                placeholders.push(name);
+            },
+            '}' if chars.next() != Some('}') => {
+                return Err("unmatched `}` in URL (use `}}` for a literal brace)".into());
+            },
+            _ => (),
+        }
+    }
+
+    Ok(placeholders)
+}
 
-impl<I: Iterator<Item = TokenTree>> DelegateParser<I> {
+/// A `delegate!(impl<..> for Type where .. { get_adapter })` invocation.
+struct Delegate {
+    generics: Generics,
+    for_type: Type,
+    get_adpt: TokenStream2,
+}
 
-    fn expect_keyword(&mut self, expect: &str) {
-        match self.0.next() {
-            Some(TokenTree::Token(Token::Ident(ref ident))) => assert_eq!(ident.as_ref(), expect),
-            Some(other) => panic!("Expected keyword {:?}, got {:?}", expect, other),
-            None => panic!("Expected keyword/ {:?}, found nothing"),
+impl Delegate {
+    fn from_mac(mac: TraitItemMacro) -> Result<Self> {
+        if !mac.mac.path.is_ident("delegate") {
+            return Err(Error::new_spanned(&mac.mac.path, "Only `delegate!()` macro invocations are \
+                                                           allowed inside service traits."));
         }
+
+        mac.mac.parse_body()
     }
 
-    fn get_generics(&mut self) -> Vec<TokenTree> {
-        let mut depth = 0;
+    /// The blanket impl used when no delegates are declared.
+    fn default_impl() -> Self {
+        parse_quote!(impl<__Adapter: ::anterofit::AbsAdapter> for __Adapter { self })
+    }
 
-        let ret = self.take_while(|token| {
-            match *token {
-                TokenTree::Token(Token::Lt) => depth += 1,
-                TokenTree::Token(Token::Gt) => {
-                    depth -= 1;
+    fn output(&self, service: &ServiceTrait) -> TokenStream2 {
+        let trait_generics = &service.item.generics;
 
-                    if depth == 0 { return true; }
-                },
-                _ => (),
+        // The impl declares the trait's generics as well as the delegate's own;
+        // lifetimes have to come first.
+        let mut generics = trait_generics.clone();
+        let (lifetimes, others): (Vec<_>, Vec<_>) = generics.params.into_iter()
+            .chain(self.generics.params.iter().cloned())
+            .partition(|param| matches!(*param, GenericParam::Lifetime(_)));
+
+        generics.params = lifetimes.into_iter().chain(others).collect();
+
+        {
+            let predicates = &mut generics.make_where_clause().predicates;
+
+            if let Some(ref where_clause) = self.generics.where_clause {
+                predicates.extend(where_clause.predicates.iter().cloned());
             }
 
-            depth > 0
-        });
+            let supertraits = &service.item.supertraits;
+            let for_type = &self.for_type;
 
-        if depth != 0 {
-            panic!("Missing closing > on generics in delegate!() invocation: {:?}", ret);
+            if !supertraits.is_empty() {
+                predicates.push(parse_quote!(#for_type: #supertraits));
+            }
         }
 
-        ret
-    }
+        let (impl_generics, _, where_clause) = generics.split_for_impl();
+        let (_, ty_generics, _) = trait_generics.split_for_impl();
+        let trait_name = &service.item.ident;
+        let for_type = &self.for_type;
+        let methods = service.methods.iter().map(|method| method.method_impl(&self.get_adpt, &service.defaults));
 
-    fn get_type(&mut self) -> Vec<TokenTree> {
-        self.take_while(|token| match *token {
-            TokenTree::Delimited(ref delimited) => delimited.delim != DelimToken::Brace,
-            TokenTree::Token(Token::Ident(ref ident)) => ident != "where",
-            _ => true,
-        })
+        quote! {
+            impl #impl_generics #trait_name #ty_generics for #for_type #where_clause {
+                #(#methods)*
+            }
+        }
     }
+}
+
+impl Parse for Delegate {
+    fn parse(input: ParseStream) -> Result<Self> {
+        input.parse::<Token![impl]>()?;
+
+        let mut generics: Generics = input.parse()?;
+
+        input.parse::<Token![for]>()?;
+
+        let for_type = input.parse()?;
 
-    fn get_where(&mut self) -> Vec<TokenTree> {
-        match self.0.peek() {
-            Some(&TokenTree::Token(Token::Ident(ref ident))) if ident == "where" => (),
-            _ => return vec![],
+        generics.where_clause = input.parse()?;
+
+        let content;
+        braced!(content in input);
+
+        let get_adpt = content.parse()?;
+
+        if !input.is_empty() {
+            return Err(input.error("Unexpected tokens after `delegate!()` body"));
         }
 
-        self.take_while(|token| match *token {
-            TokenTree::Delimited(ref delimited) => delimited.delim != DelimToken::Brace,
-            _ => true,
+        Ok(Delegate {
+            generics: generics,
+            for_type: for_type,
+            get_adpt: get_adpt,
         })
     }
+}
 
-    fn get_body_inner(&mut self) -> Vec<TokenTree> {
-        let delimited = match self.0.next() {
-            Some(TokenTree::Delimited(delimited)) => delimited,
-            Some(TokenTree::Token(token)) => panic!("Expected opening brace, got {:?}", token),
-            None => panic!("Expected opening brace, got nothing"),
-        };
-
-        assert_eq!(delimited.delim, DelimToken::Brace);
+#[test]
+fn test_url_placeholders() {
+    assert_eq!(url_placeholders("/users/{id}/posts/{{x}}/{post_id}").unwrap(), ["id", "post_id"]);
+    assert!(url_placeholders("/users/{}").is_err());
+    assert!(url_placeholders("/users/{id").is_err());
+    assert!(url_placeholders("/users/id}").is_err());
+}
 
-        delimited.tts
+#[test]
+fn test_expansion_errors() {
+    fn expand(input: TokenStream2) -> Result<TokenStream2> {
+        ServiceTrait::from_item(parse2(input)?).map(|service| service.output())
     }
 
-    fn take_while<F>(&mut self, mut predicate: F) -> Vec<TokenTree> where F: FnMut(&TokenTree) -> bool {
-        let mut out = vec![];
+    let out = expand(quote! {
+        #[prefix("/api")]
+        #[header("Accept", "application/json")]
+        trait UserService<T>: Send where T: Clone {
+            #[get("/users/{id}")]
+            fn get_user(&self, #[path] id: u64, #[query("fields")] mut fields: &str) -> T;
 
-        loop {
-            if !self.0.peek().map_or(false, &mut predicate) {
-                break
-            }
-
-            out.push(self.0.next().unwrap())
+            delegate!(impl<A: AsRef<Adapter>> for Wrapper<A> { self.0.as_ref() });
         }
+    }).unwrap().to_string();
 
-        out
-    }
-}
\ No newline at end of file
+    assert!(out.contains("GET (\"/users/{id}\" , id = id) ; query ! { \"fields\" => fields }"), "{}", out);
+    assert!(out.contains("[prefix ! (\"/api\") ; headers ! { \"Accept\" => \"application/json\" }]"), "{}", out);
+    assert!(!out.contains("# [prefix"), "{}", out);
+    assert!(out.contains("impl < T , A : AsRef < Adapter > > UserService < T > for Wrapper < A > \
+                          where T : Clone , Wrapper < A > : Send"), "{}", out);
+
+    let err = |input| expand(input).expect_err("expected an error").to_string();
+
+    assert!(err(quote! {
+        trait Typo { #[get("/users/{idd}")] fn get_user(&self, #[path] id: u64); }
+    }).contains("has no matching placeholder"));
+
+    assert!(err(quote! {
+        trait NoVerb { fn get_user(&self, id: u64); }
+    }).contains("must have a body"));
+
+    assert!(!err(quote! {
+        trait BadDelegate { delegate!(impl for { self }); }
+    }).is_empty());
+}
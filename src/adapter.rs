@@ -1,13 +1,17 @@
 use hyper::Url;
 use hyper::client::Client;
+use hyper::net::{HttpConnector, NetworkConnector, NetworkStream};
 
 use std::sync::Arc;
+use std::time::Duration;
 use std::fmt;
 
-use executor::{DefaultExecutor, Executor};
+use executor::{DefaultExecutor, Executor, OverflowPolicy, QueueStats, ShutdownReport};
 
 use mpmc::{self, Sender};
 
+use net::connect::Connect;
+
 use net::intercept::{Interceptor, Chain, NoIntercept};
 
 use serialize::{self, Serializer, Deserializer};
@@ -16,10 +20,16 @@ use serialize::FromStrDeserializer;
 
 use UnsizeService;
 
+// The connector set with `AdapterBuilder::connector()`, and a function to build the default
+// client with it
+type ConnectorParts = (Arc<Connect>, Box<Fn() -> Client + Send + Sync>);
+
 /// A builder for `Adapter`. Call `Adapter::builder()` to get an instance.
 pub struct AdapterBuilder<S, D, E, I> {
     base_url: Option<Url>,
     client: Option<Client>,
+    connector: Option<ConnectorParts>,
+    queue_bound: Option<(usize, OverflowPolicy)>,
     executor: E,
     interceptor: I,
     serializer: S,
@@ -31,6 +41,8 @@ impl AdapterBuilder<NoSerializer, FromStrDeserializer, DefaultExecutor, NoInterc
         AdapterBuilder {
             base_url: None,
             client: None,
+            connector: None,
+            queue_bound: None,
             executor: DefaultExecutor::new(),
             interceptor: NoIntercept,
             serializer: NoSerializer,
@@ -49,18 +61,50 @@ impl<S, D, E, I> AdapterBuilder<S, D, E, I> {
 
     /// Set a `hyper::Client` instance to use with the adapter.
     ///
-    /// If not supplied, a default instance will be constructed.
+    /// If not supplied, a default instance will be constructed, using the connector set with
+    /// `connector()` if any. A client supplied here is used as-is, regardless of `connector()`,
+    /// which then only applies to upgraded connections.
     pub fn client(mut self, client: Client) -> Self {
         self.client = Some(client);
         self
     }
 
+    /// Set the connector used to open connections, such as one providing TLS.
+    ///
+    /// `connector` is used for connections that are upgraded to other protocols, such as
+    /// WebSockets, and, unless a client is set with `client()`, for the default
+    /// `hyper::Client`; the order of the two calls does not matter. Connections are opened with
+    /// `HttpConnector` by default.
+    pub fn connector<C>(mut self, connector: C) -> Self
+    where C: NetworkConnector + Clone + Send + Sync + 'static, C::Stream: NetworkStream + Send {
+        let client_connector = connector.clone();
+        let client = move || Client::with_connector(client_connector.clone());
+
+        self.connector = Some((Arc::new(connector), Box::new(client)));
+        self
+    }
+
+    /// Limit the executor queue to `capacity` pending requests, applying `policy` when a request
+    /// is submitted while the queue is full.
+    ///
+    /// By default, the queue is unbounded.
+    ///
+    /// ## Panics
+    /// If `capacity` is zero.
+    pub fn bounded_queue(mut self, capacity: usize, policy: OverflowPolicy) -> Self {
+        assert!(capacity > 0, "Executor queue capacity must be nonzero");
+        self.queue_bound = Some((capacity, policy));
+        self
+    }
+
     /// Set a new executor for the adapter.
     pub fn executor<E_>(self, executor: E_) -> AdapterBuilder<S, D, E_, I>
         where E: Executor {
         AdapterBuilder {
             base_url: self.base_url,
             client: self.client,
+            connector: self.connector,
+            queue_bound: self.queue_bound,
             executor: executor,
             interceptor: self.interceptor,
             serializer: self.serializer,
@@ -74,6 +118,8 @@ impl<S, D, E, I> AdapterBuilder<S, D, E, I> {
         AdapterBuilder {
             base_url: self.base_url,
             client: self.client,
+            connector: self.connector,
+            queue_bound: self.queue_bound,
             executor: self.executor,
             interceptor: interceptor,
             serializer: self.serializer,
@@ -87,6 +133,8 @@ impl<S, D, E, I> AdapterBuilder<S, D, E, I> {
         AdapterBuilder {
             base_url: self.base_url,
             client: self.client,
+            connector: self.connector,
+            queue_bound: self.queue_bound,
             executor: self.executor,
             interceptor: self.interceptor.chain(next),
             serializer: self.serializer,
@@ -100,6 +148,8 @@ impl<S, D, E, I> AdapterBuilder<S, D, E, I> {
         AdapterBuilder {
             base_url: self.base_url,
             client: self.client,
+            connector: self.connector,
+            queue_bound: self.queue_bound,
             executor: self.executor,
             interceptor: self.interceptor,
             serializer: serialize,
@@ -113,6 +163,8 @@ impl<S, D, E, I> AdapterBuilder<S, D, E, I> {
         AdapterBuilder {
             base_url: self.base_url,
             client: self.client,
+            connector: self.connector,
+            queue_bound: self.queue_bound,
             executor: self.executor,
             interceptor: self.interceptor,
             serializer: self.serializer,
@@ -132,6 +184,20 @@ impl<S, D, E, I> AdapterBuilder<S, D, E, I> {
     }
 }
 
+#[cfg(feature = "serde-xml-rs")]
+impl<S, D, E, I> AdapterBuilder<S, D, E, I> {
+    /// Convenience method for using XML serialization.
+    ///
+    /// The root element of request bodies is named after the serialized type; use
+    /// `serializer(xml::Serializer::new().root(..))` to set the name instead.
+    ///
+    /// Enabled with the `xml` feature.
+    pub fn serialize_xml(self) -> AdapterBuilder<serialize::xml::Serializer, serialize::xml::Deserializer, E, I> {
+        self.serializer(serialize::xml::Serializer::new())
+            .deserializer(serialize::xml::Deserializer)
+    }
+}
+
 impl<S, D, E, I> AdapterBuilder<S, D, E, I>
 where S: Serializer, D: Deserializer, E: Executor, I: Interceptor {
 
@@ -139,16 +205,26 @@ where S: Serializer, D: Deserializer, E: Executor, I: Interceptor {
     ///
     /// `<E as Executor>::start()` will be called here.
     pub fn build(self) -> Adapter<S, D> {
-        let (tx, rx) = mpmc::channel();
+        let (tx, rx) = match self.queue_bound {
+            Some((capacity, policy)) => mpmc::bounded(capacity, policy),
+            None => mpmc::channel(),
+        };
 
         self.executor.start(rx);
 
+        let (connector, client) = match (self.connector, self.client) {
+            (Some((connector, _)), Some(client)) => (connector, client),
+            (Some((connector, default_client)), None) => (connector, default_client()),
+            (None, client) => (Arc::new(HttpConnector) as Arc<Connect>, client.unwrap_or_else(Client::new)),
+        };
+
         let consts = AdapterConsts {
             base_url: self.base_url,
-            client: self.client.unwrap_or_else(Client::new),
+            client: Arc::new(client),
+            connector: connector,
             serializer: self.serializer,
             deserializer: self.deserializer,
-            sender: tx,
+            sender: Arc::new(tx),
         };
 
         Adapter {
@@ -198,6 +274,189 @@ impl<S, D> Adapter<S, D> {
     pub fn interceptor_mut(&mut self) -> InterceptorMut {
         InterceptorMut(&mut Arc::make_mut(&mut self.inner).interceptor)
     }
+
+    /// Get a snapshot of the state of this adapter's executor queue, such as the number of
+    /// pending requests and active workers.
+    pub fn queue_stats(&self) -> QueueStats {
+        self.inner.consts.sender.stats()
+    }
+
+    /// Gracefully shut down this adapter's executor.
+    ///
+    /// New requests will be rejected with `Error::Shutdown`, while requests already in the
+    /// queue will continue to be executed. This blocks until the executor's workers have
+    /// finished all remaining requests and quit, or `timeout` elapses, whichever comes first.
+    ///
+    /// Requests still in the queue when `timeout` elapses are discarded and their `Call`s
+    /// will yield `Error::Abandoned`; the returned report counts them along with any requests
+    /// that were still executing.
+    ///
+    /// ## Note
+    /// This affects all copies of this adapter and all service trait objects created from it,
+    /// as well as the adapter it was scoped from and all other adapters created with `scoped()`
+    /// from either, as they share one executor.
+    ///
+    /// Calling this from an executor thread (e.g. in an `on_complete()` callback) will block
+    /// until `timeout` elapses, as that worker cannot quit while it is waiting.
+    pub fn shutdown(&self, timeout: Duration) -> ShutdownReport {
+        self.inner.consts.sender.shutdown(timeout)
+    }
+
+    /// Start building a view of this adapter with its own settings, such as for use with only
+    /// some service traits.
+    ///
+    /// The new adapter shares this adapter's executor and `hyper::Client`, and starts with copies
+    /// of its base URL, interceptor, serializer and deserializer, any of which can be replaced.
+    /// Calling `shutdown()` on either adapter shuts down the shared executor for both.
+    ///
+    /// ```rust,no_run
+    /// # #[macro_use] extern crate anterofit;
+    /// # fn main() {
+    /// use anterofit::{Adapter, Url};
+    /// use anterofit::net::intercept::AddHeader;
+    /// use anterofit::net::header::{Authorization, Bearer};
+    ///
+    /// service! {
+    ///     trait BillingService {
+    ///         fn balance(&self) -> String {
+    ///             GET("/balance")
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let adapter = Adapter::builder()
+    ///     .base_url(Url::parse("https://api.example.com").unwrap())
+    ///     .build();
+    ///
+    /// // Only requests made through `billing` are authorized.
+    /// let billing = adapter.scoped()
+    ///     .base_url(Url::parse("https://billing.example.com").unwrap())
+    ///     .chain_interceptor(AddHeader(Authorization(Bearer { token: "hunter2".into() })))
+    ///     .build();
+    ///
+    /// let balance = billing.balance().exec_here().unwrap();
+    /// # }
+    /// ```
+    pub fn scoped(&self) -> ScopedBuilder<S, D> where S: Clone, D: Clone {
+        let consts = &self.inner.consts;
+
+        ScopedBuilder {
+            base_url: consts.base_url.clone(),
+            client: consts.client.clone(),
+            connector: consts.connector.clone(),
+            sender: consts.sender.clone(),
+            interceptor: self.inner.interceptor.clone(),
+            serializer: consts.serializer.clone(),
+            deserializer: consts.deserializer.clone(),
+        }
+    }
+}
+
+/// A builder for a view of an existing adapter. Call `Adapter::scoped()` to get an instance.
+pub struct ScopedBuilder<S, D> {
+    base_url: Option<Url>,
+    client: Arc<Client>,
+    connector: Arc<Connect>,
+    sender: Arc<Sender>,
+    interceptor: Option<Arc<Interceptor>>,
+    serializer: S,
+    deserializer: D,
+}
+
+impl<S, D> ScopedBuilder<S, D> {
+    /// Set the base URL that the new adapter will use for all requests.
+    pub fn base_url(self, url: Url) -> Self {
+        ScopedBuilder { base_url: Some(url), .. self }
+    }
+
+    /// Remove the base URL, so that all service method URLs are assumed to be absolute.
+    pub fn no_base_url(self) -> Self {
+        ScopedBuilder { base_url: None, .. self }
+    }
+
+    /// Set a new interceptor for the new adapter, replacing the one copied from the original.
+    pub fn interceptor<I>(self, interceptor: I) -> Self where I: Interceptor {
+        ScopedBuilder { interceptor: interceptor.into_opt_obj(), .. self }
+    }
+
+    /// Chain a new interceptor after the one copied from the original adapter.
+    pub fn chain_interceptor<I>(self, next: I) -> Self where I: Interceptor {
+        let interceptor = match self.interceptor {
+            Some(ref current) => current.clone().chain(next).into_opt_obj(),
+            None => next.into_opt_obj(),
+        };
+
+        ScopedBuilder { interceptor: interceptor, .. self }
+    }
+
+    /// Set a new `Serializer` impl for the new adapter.
+    pub fn serializer<S_>(self, serialize: S_) -> ScopedBuilder<S_, D>
+    where S_: Serializer {
+        ScopedBuilder {
+            base_url: self.base_url,
+            client: self.client,
+            connector: self.connector,
+            sender: self.sender,
+            interceptor: self.interceptor,
+            serializer: serialize,
+            deserializer: self.deserializer,
+        }
+    }
+
+    /// Set a new `Deserializer` impl for the new adapter.
+    pub fn deserializer<D_>(self, deserialize: D_) -> ScopedBuilder<S, D_>
+    where D_: Deserializer {
+        ScopedBuilder {
+            base_url: self.base_url,
+            client: self.client,
+            connector: self.connector,
+            sender: self.sender,
+            interceptor: self.interceptor,
+            serializer: self.serializer,
+            deserializer: deserialize,
+        }
+    }
+}
+
+#[cfg(feature = "serde_json")]
+impl<S, D> ScopedBuilder<S, D> {
+    /// Convenience method for using JSON serialization.
+    pub fn serialize_json(self) -> ScopedBuilder<serialize::json::Serializer, serialize::json::Deserializer> {
+        self.serializer(serialize::json::Serializer)
+            .deserializer(serialize::json::Deserializer)
+    }
+}
+
+#[cfg(feature = "serde-xml-rs")]
+impl<S, D> ScopedBuilder<S, D> {
+    /// Convenience method for using XML serialization.
+    pub fn serialize_xml(self) -> ScopedBuilder<serialize::xml::Serializer, serialize::xml::Deserializer> {
+        self.serializer(serialize::xml::Serializer::new())
+            .deserializer(serialize::xml::Deserializer)
+    }
+}
+
+impl<S, D> ScopedBuilder<S, D> where S: Serializer, D: Deserializer {
+    /// Complete the new adapter.
+    pub fn build(self) -> Adapter<S, D> {
+        let consts = AdapterConsts {
+            base_url: self.base_url,
+            client: self.client,
+            connector: self.connector,
+            serializer: self.serializer,
+            deserializer: self.deserializer,
+            sender: self.sender,
+        };
+
+        Adapter {
+            inner: Arc::new(
+                Adapter_ {
+                    consts: Arc::new(consts),
+                    interceptor: self.interceptor,
+                }
+            ),
+        }
+    }
 }
 
 impl<S, D> fmt::Debug for Adapter_<S, D>
@@ -268,8 +527,10 @@ impl<'a> InterceptorMut<'a> {
 /// Constant types in an adapter
 pub struct AdapterConsts<S, D> {
     pub base_url: Option<Url>,
-    pub client: Client,
-    pub sender: Sender,
+    // Shared with scoped adapters
+    pub client: Arc<Client>,
+    pub connector: Arc<Connect>,
+    pub sender: Arc<Sender>,
     pub serializer: S,
     pub deserializer: D,
 }
@@ -352,3 +613,69 @@ impl<S, D> PrivAdapter for Adapter_<S, D> where S: Serializer, D: Deserializer {
         self.interceptor.clone()
     }
 }
+
+#[test]
+fn scoped_adapter_shares_executor() {
+    let adapter = Adapter::builder()
+        .base_url(Url::parse("http://example.com/").unwrap())
+        .build();
+
+    let scoped = adapter.scoped()
+        .base_url(Url::parse("http://example.org/").unwrap())
+        .chain_interceptor(|_: &mut ::net::RequestHead| ())
+        .build();
+
+    let (consts, scoped_consts) = (&adapter.inner.consts, &scoped.inner.consts);
+
+    assert!(Arc::ptr_eq(&consts.sender, &scoped_consts.sender));
+    assert!(Arc::ptr_eq(&consts.client, &scoped_consts.client));
+    assert_eq!(consts.base_url.as_ref().unwrap().as_str(), "http://example.com/");
+    assert_eq!(scoped_consts.base_url.as_ref().unwrap().as_str(), "http://example.org/");
+    assert!(adapter.inner.interceptor.is_none() && scoped.inner.interceptor.is_some());
+}
+
+#[test]
+fn connector_builds_default_client() {
+    use hyper::net::HttpStream;
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use net::method::Get;
+    use net::request::RequestBuilder;
+
+    // Counts the connections it opens
+    #[derive(Clone)]
+    struct Counting(Arc<AtomicUsize>);
+
+    impl NetworkConnector for Counting {
+        type Stream = HttpStream;
+
+        fn connect(&self, host: &str, port: u16, scheme: &str) -> ::hyper::Result<HttpStream> {
+            self.0.fetch_add(1, Ordering::AcqRel);
+            NetworkConnector::connect(&HttpConnector, host, port, scheme)
+        }
+    }
+
+    // Nothing is listening on this port; requests fail after connecting
+    let url = {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        Url::parse(&format!("http://127.0.0.1:{}/", listener.local_addr().unwrap().port())).unwrap()
+    };
+
+    let connects = |client: Option<Client>| {
+        let count = Arc::new(AtomicUsize::new(0));
+        let builder = Adapter::builder().base_url(url.clone());
+        let builder = match client {
+            Some(client) => builder.client(client),
+            None => builder,
+        };
+
+        let adapter = builder.connector(Counting(count.clone())).build();
+
+        assert!(RequestBuilder::new(&adapter, Get, "".into()).build::<()>().exec_here().is_err());
+        count.load(Ordering::Acquire)
+    };
+
+    assert_eq!(connects(None), 1);
+    assert_eq!(connects(Some(Client::new())), 0);
+}
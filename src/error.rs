@@ -102,6 +102,20 @@ quick_error! {
         ResultTaken {
             description("The result has already been taken from this Call.")
         }
+        /// Returned by `Request::exec()` when the executor queue is full and its overflow
+        /// policy is `OverflowPolicy::Reject`.
+        QueueFull {
+            description("The executor queue is full.")
+        }
+        /// Returned by `Request::exec()` after `Adapter::shutdown()` has been called.
+        Shutdown {
+            description("The adapter's executor has been shut down.")
+        }
+        /// The request was discarded from the executor queue before it could be executed,
+        /// e.g. by `OverflowPolicy::DropOldest` or `Adapter::shutdown()`.
+        Abandoned {
+            description("The request was discarded before it could be executed.")
+        }
     }
 }
 
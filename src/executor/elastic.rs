@@ -0,0 +1,287 @@
+//! An executor which grows and shrinks its thread pool with load, using work-stealing to
+//! balance jobs between threads.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::{self, Builder};
+use std::time::Duration;
+
+use super::{Executor, Receiver, RecvTimeoutError};
+
+/// The default time an idle worker will wait for a job before quitting.
+pub const DEFAULT_IDLE_TIMEOUT_SECS: u64 = 60;
+
+/// The maximum number of jobs a worker will take from the queue at once.
+///
+/// Jobs beyond the first are kept in the worker's local queue, where other workers can
+/// steal them.
+const BATCH_SIZE: usize = 4;
+
+/// An executor which runs between `min` and `max` worker threads.
+///
+/// Additional workers are spawned when a request is submitted while all current workers are busy,
+/// up to `max`. Workers which remain idle for longer than the idle timeout will quit,
+/// down to `min`.
+///
+/// While all workers are busy, each takes a small batch of jobs from the adapter's queue at a
+/// time, which reduces contention on the queue. Batches are kept in per-worker queues and run
+/// in priority order; a worker takes a waiting job of higher priority from the shared queue
+/// before continuing its batch, and idle workers steal from the batches of busy workers so jobs
+/// are not held up behind long-running requests. A new job wakes a single idle worker rather
+/// than all of them.
+///
+/// See `Receiver::try_recv_batch()` for how batched jobs count against a bounded queue and
+/// `Adapter::shutdown()`.
+#[derive(Debug)]
+pub struct Elastic {
+    min: usize,
+    max: usize,
+    idle_timeout: Duration,
+}
+
+impl Elastic {
+    /// Create a new elastic executor which runs between `min` and `max` worker threads.
+    ///
+    /// The `min` threads will not be spawned until `Executor::start()` is called.
+    ///
+    /// ## Panics
+    /// If `max` is zero or less than `min`.
+    pub fn new(min: usize, max: usize) -> Self {
+        assert!(max > 0, "Elastic executor must allow at least one thread");
+        assert!(min <= max, "Elastic executor `min` ({}) is greater than `max` ({})", min, max);
+
+        Elastic {
+            min: min,
+            max: max,
+            idle_timeout: Duration::from_secs(DEFAULT_IDLE_TIMEOUT_SECS),
+        }
+    }
+
+    /// Set the duration a worker will wait for a job before quitting, if there are more
+    /// than `min` workers running.
+    ///
+    /// Defaults to `DEFAULT_IDLE_TIMEOUT_SECS`.
+    pub fn idle_timeout(self, idle_timeout: Duration) -> Self {
+        Elastic { idle_timeout: idle_timeout, .. self }
+    }
+}
+
+impl Executor for Elastic {
+    /// Spawn `min` worker threads and register the queue's backlog callback
+    /// to spawn more on demand. The threads will be named such that they
+    /// can easily be associated with Anterofit.
+    ///
+    /// If a panic occurs on a worker thread, it will be restarted under the same name.
+    ///
+    /// ## Panics
+    /// If a worker thread failed to spawn.
+    fn start(self, recv: Receiver) {
+        let pool = Arc::new(Pool {
+            min: self.min,
+            max: self.max,
+            idle_timeout: self.idle_timeout,
+            threads: AtomicUsize::new(0),
+            idle: AtomicUsize::new(0),
+            next_id: AtomicUsize::new(0),
+            recv: recv,
+        });
+
+        for _ in 0 .. self.min {
+            pool.try_spawn();
+        }
+
+        // The queue drops this callback when it is closed, so the pool will be freed
+        // once all workers have quit.
+        let pool_ = pool.clone();
+
+        pool.recv.on_backlog(move || {
+            if pool_.idle.load(Ordering::Acquire) == 0 {
+                pool_.try_spawn();
+            }
+        });
+    }
+}
+
+struct Pool {
+    min: usize,
+    max: usize,
+    idle_timeout: Duration,
+    threads: AtomicUsize,
+    idle: AtomicUsize,
+    next_id: AtomicUsize,
+    recv: Receiver,
+}
+
+impl Pool {
+    /// Spawn a new worker if there are fewer than `max`.
+    fn try_spawn(self: &Arc<Self>) {
+        let mut threads = self.threads.load(Ordering::Acquire);
+
+        loop {
+            if threads >= self.max { return; }
+
+            match self.threads.compare_exchange_weak(threads, threads + 1,
+                                                     Ordering::AcqRel, Ordering::Acquire) {
+                Ok(_) => break,
+                Err(current) => threads = current,
+            }
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+
+        spawn_worker(WorkerState {
+            id: id,
+            recv: self.recv.clone(),
+            pool: self.clone(),
+        });
+    }
+
+    /// Decrement the thread count if there are more than `min` workers, returning `true` if so.
+    fn try_retire(&self) -> bool {
+        let mut threads = self.threads.load(Ordering::Acquire);
+
+        loop {
+            if threads <= self.min { return false; }
+
+            match self.threads.compare_exchange_weak(threads, threads - 1,
+                                                     Ordering::AcqRel, Ordering::Acquire) {
+                Ok(_) => return true,
+                Err(current) => threads = current,
+            }
+        }
+    }
+}
+
+struct WorkerState {
+    id: usize,
+    recv: Receiver,
+    pool: Arc<Pool>,
+}
+
+impl WorkerState {
+    fn run(&mut self) {
+        loop {
+            if let Some(job) = self.recv.try_recv_batch(BATCH_SIZE) {
+                job.exec();
+                continue;
+            }
+
+            self.pool.idle.fetch_add(1, Ordering::AcqRel);
+            let res = self.recv.recv_timeout(self.pool.idle_timeout);
+            self.pool.idle.fetch_sub(1, Ordering::AcqRel);
+
+            match res {
+                Ok(job) => job.exec(),
+                Err(RecvTimeoutError::Timeout) => if self.pool.try_retire() { return; },
+                Err(RecvTimeoutError::Closed) => {
+                    self.pool.threads.fetch_sub(1, Ordering::AcqRel);
+                    return;
+                },
+            }
+        }
+    }
+}
+
+impl Drop for WorkerState {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            spawn_worker(WorkerState {
+                id: self.id,
+                recv: self.recv.clone(),
+                pool: self.pool.clone(),
+            });
+        }
+    }
+}
+
+fn spawn_worker(mut state: WorkerState) {
+    let _ = Builder::new()
+        .name(format!("anterofit_elastic_worker_{}", state.id))
+        .spawn(move || state.run())
+        .expect("Failed to spawn Anterofit worker thread");
+}
+
+#[test]
+fn elastic_grows_and_drains() {
+    use std::sync::atomic::AtomicUsize;
+
+    use executor::Priority;
+
+    let (tx, rx) = ::mpmc::channel();
+
+    Elastic::new(0, 4).idle_timeout(Duration::from_millis(50)).start(rx);
+
+    let counter = Arc::new(AtomicUsize::new(0));
+
+    for _ in 0 .. 32 {
+        let counter = counter.clone();
+
+        tx.send(Box::new(move || {
+            thread::sleep(Duration::from_millis(1));
+            counter.fetch_add(1, Ordering::Relaxed);
+        }), Priority::Normal).unwrap();
+    }
+
+    assert!(tx.stats().receivers > 1);
+
+    let report = tx.shutdown(Duration::from_secs(10));
+
+    assert!(report.is_clean(), "{:?}", report);
+    assert_eq!(counter.load(Ordering::Relaxed), 32);
+}
+
+#[test]
+fn elastic_runs_in_order() {
+    use std::sync::Mutex;
+
+    use executor::Priority;
+
+    let (tx, rx) = ::mpmc::channel();
+    let order = Arc::new(Mutex::new(Vec::new()));
+
+    // Queue the jobs before the only worker starts, so they are all taken from a backlog
+    for i in 0 .. 16 {
+        let order = order.clone();
+        tx.send(Box::new(move || order.lock().unwrap().push(i)), Priority::Normal).unwrap();
+    }
+
+    Elastic::new(1, 1).start(rx);
+
+    assert!(tx.shutdown(Duration::from_secs(10)).is_clean());
+    assert_eq!(*order.lock().unwrap(), (0 .. 16).collect::<Vec<_>>());
+}
+
+#[test]
+fn elastic_steals_from_busy_worker() {
+    use std::sync::mpsc;
+
+    use executor::Priority;
+
+    let (tx, rx) = ::mpmc::channel();
+    let (blocked_tx, blocked) = mpsc::channel();
+    let (done_tx, done) = mpsc::channel();
+
+    // The first job blocks its worker until all the others have run, including any
+    // batched behind it
+    tx.send(Box::new(move || {
+        blocked_tx.send(done.recv_timeout(Duration::from_secs(10)).is_ok()).unwrap();
+    }), Priority::Normal).unwrap();
+
+    let remaining = Arc::new(AtomicUsize::new(15));
+
+    for _ in 0 .. 15 {
+        let remaining = remaining.clone();
+        let done_tx = done_tx.clone();
+
+        tx.send(Box::new(move || {
+            if remaining.fetch_sub(1, Ordering::AcqRel) == 1 {
+                done_tx.send(()).unwrap();
+            }
+        }), Priority::Normal).unwrap();
+    }
+
+    Elastic::new(2, 2).start(rx);
+
+    assert!(blocked.recv().unwrap(), "jobs were held up behind a blocked worker");
+    assert!(tx.shutdown(Duration::from_secs(10)).is_clean());
+}
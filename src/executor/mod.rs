@@ -2,13 +2,114 @@
 
 #![cfg_attr(feature="clippy", allow(boxed_local))]
 
+pub mod elastic;
+
 pub mod threaded;
 
 pub use mpmc::{Receiver, RecvIter, RecvIntoIter};
 
+/// Error returned by `Receiver::recv_timeout()`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RecvTimeoutError {
+    /// No job was received before the timeout elapsed.
+    Timeout,
+    /// The job queue is closed; the executor can quit.
+    Closed,
+}
+
 /// The default executor which should be suitable for most use-cases.
 pub type DefaultExecutor = threaded::SingleThread;
 
+/// What to do when a job is submitted to an executor queue which is already at capacity.
+///
+/// Set with `AdapterBuilder::bounded_queue()`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Block the submitting thread until there is room in the queue.
+    ///
+    /// ## Warning
+    /// Submitting requests from an executor thread (e.g. in an `on_complete()` callback) with
+    /// this policy may deadlock if all executor threads end up waiting on the queue.
+    Block,
+    /// Don't queue the request; the `Call` returned by `Request::exec()` will immediately
+    /// yield `Error::QueueFull`.
+    Reject,
+    /// Discard the oldest job in the queue to make room for the new one.
+    ///
+    /// The `Call` of the discarded request will yield `Error::Abandoned`.
+    DropOldest,
+}
+
+/// The priority of a request in the executor queue.
+///
+/// Set with `Request::priority()`; requests with higher priorities are executed first.
+/// Requests of equal priority are executed in the order they were submitted.
+///
+/// To prevent starvation, a waiting request's effective priority rises as higher-priority
+/// requests are executed ahead of it, so a steady stream of high-priority requests will not hold
+/// back lower-priority ones indefinitely.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// For background work which can wait, e.g. prefetching or syncing.
+    Low,
+    /// The default priority.
+    #[default]
+    Normal,
+    /// For latency-sensitive work, e.g. fetching data the user is waiting on.
+    High,
+}
+
+impl Priority {
+    #[doc(hidden)]
+    pub fn level(self) -> i64 {
+        match self {
+            Priority::Low => 0,
+            Priority::Normal => 1,
+            Priority::High => 2,
+        }
+    }
+}
+
+/// A snapshot of the state of an executor queue.
+///
+/// Returned by `Adapter::queue_stats()` and `Receiver::stats()`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct QueueStats {
+    /// The number of jobs waiting in the queue, including those batched by workers
+    /// which have not started yet.
+    pub queued: usize,
+    /// The maximum number of jobs the queue will hold, if bounded.
+    pub capacity: Option<usize>,
+    /// The number of live `Receiver`s for the queue, generally equal to the number of worker
+    /// threads once the executor has started.
+    pub receivers: usize,
+    /// The number of workers currently executing a job.
+    pub active: usize,
+}
+
+/// The result of `Adapter::shutdown()`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ShutdownReport {
+    /// The number of requests which were still queued when the timeout elapsed.
+    ///
+    /// These were discarded and their `Call`s will yield `Error::Abandoned`.
+    pub abandoned: usize,
+    /// The number of requests which were still executing when the timeout elapsed.
+    ///
+    /// These cannot be interrupted and will complete in the background.
+    pub in_flight: usize,
+    /// The number of workers which had not yet exited when the timeout elapsed.
+    pub workers: usize,
+}
+
+impl ShutdownReport {
+    /// Returns `true` if every queued request was completed and all workers exited before the
+    /// timeout elapsed.
+    pub fn is_clean(&self) -> bool {
+        self.abandoned == 0 && self.in_flight == 0 && self.workers == 0
+    }
+}
+
 /// A trait describing a type which can execute tasks (in the background or otherwise).
 ///
 /// Invoking `ExecBox` *may* panic, so the executor should
@@ -16,6 +117,8 @@ pub trait Executor {
     /// Initialize the executor, polling `recv` for jobs.
     ///
     /// When `Receiver::recv()` returns `None`, the job queue is closed and the executor can quit.
+    /// Workers should drop their `Receiver`s when they quit, as `Adapter::shutdown()` waits
+    /// for all of them to be dropped.
     fn start(self, recv: Receiver);
 }
 
@@ -169,15 +169,21 @@ extern crate quick_error;
 
 extern crate futures;
 
-extern crate crossbeam;
 extern crate parking_lot;
 
 extern crate multipart;
 
+#[macro_use]
 extern crate serde;
 
 extern crate url;
 
+#[cfg(feature = "prost")]
+extern crate prost;
+
+#[cfg(feature = "tungstenite")]
+pub extern crate tungstenite;
+
 pub extern crate hyper;
 
 mod adapter;
@@ -201,7 +207,7 @@ pub use error::Error;
 
 pub use hyper::Url;
 
-pub use adapter::{Adapter, AbsAdapter, AdapterBuilder, InterceptorMut};
+pub use adapter::{Adapter, AbsAdapter, AdapterBuilder, InterceptorMut, ScopedBuilder};
 
 #[cfg(feature = "serde_json")]
 pub use adapter::JsonAdapter;
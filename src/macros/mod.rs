@@ -37,6 +37,108 @@ mod request;
 /// }
 /// ```
 ///
+/// ##URL Templates
+/// Method parameters can be substituted into the URL by name. A placeholder which doesn't name
+/// a parameter (or other variable in scope) is a compile error.
+///
+/// Parameters substituted into named placeholders are percent-encoded as a single path segment
+/// (see `net::PathSegment`); for example, a `/` in a user ID will not change the route.
+/// If the URL has format arguments, a placeholder must name one of them (e.g.
+/// `user_id = user_id`) to be encoded, rather than a parameter directly.
+///
+/// Because the URL is a format string, literal braces in it must be doubled (`{{` and `}}`),
+/// as with `format!()`, even if there are no format arguments.
+///
+/// ```rust
+/// # #[macro_use] extern crate anterofit;
+/// service! {
+///     pub trait PostService {
+///         fn user_post(&self, user_id: &str, post_id: u64) -> String {
+///             GET("/users/{user_id}/posts/{post_id}");
+///             with_builder!(|builder| assert_eq!(builder.head().get_url(), "/users/a%2Fb/posts/7"))
+///         }
+///     }
+/// }
+///
+/// # fn main() {
+/// let adapter = anterofit::Adapter::builder().build();
+/// let _ = adapter.user_post("a/b", 7);
+/// # }
+/// ```
+///
+/// Named format arguments which are parameter names are encoded the same way. Positional
+/// format arguments and other expressions are formatted as-is, as with `format!()`, and the
+/// integer formatting traits are supported:
+///
+/// ```rust
+/// # #[macro_use] extern crate anterofit;
+/// service! {
+///     pub trait PageService {
+///         fn next_page(&self, book: &str, page: u64, section: String) -> String {
+///             GET("/{book}/{}/{}/pages/{:x}/{next}", section.into_bytes().len(), page, page,
+///                 book = book, next = page + 1);
+///             with_builder!(|builder| assert_eq!(builder.head().get_url(), "/a%2Fb/5/31/pages/1f/32"))
+///         }
+///
+///         fn file(&self, path: &str) -> String {
+///             GET("/files/{}", path);
+///             with_builder!(|builder| assert_eq!(builder.head().get_url(), "/files/a/b"))
+///         }
+///     }
+/// }
+///
+/// # fn main() {
+/// let adapter = anterofit::Adapter::builder().build();
+/// let _ = adapter.next_page("a/b", 31, "a b c".into());
+/// let _ = adapter.file("a/b");
+/// # }
+/// ```
+///
+/// ```rust,compile_fail
+/// # #[macro_use] extern crate anterofit;
+/// # fn main() {}
+/// service! {
+///     pub trait TypoService {
+///         // error: cannot find value `usr_id` in this scope
+///         fn user(&self, user_id: u64) -> String {
+///             GET("/users/{usr_id}")
+///         }
+///     }
+/// }
+/// ```
+///
+/// ##Default Steps
+/// A `defaults` block before the methods of a service trait declares steps which are applied to
+/// every request the service makes, before the steps in each method body. This is useful
+/// for a common path prefix, headers or query pairs, without affecting other services which
+/// share the same adapter (as an `Interceptor` would).
+///
+/// ```rust
+/// # #[macro_use] extern crate anterofit;
+/// service! {
+///     pub trait RepoService {
+///         defaults {
+///             prefix!("/api/v3");
+///             headers! { "Accept" => "application/vnd.github.v3+json" };
+///             query! { "per_page" => 100 }
+///         }
+///
+///         fn repos(&self, user: &str) -> Vec<String> {
+///             GET("/users/{user}/repos");
+///             with_builder!(|builder| {
+///                 assert_eq!(builder.head().get_url(), "/api/v3/users/octocat/repos");
+///                 assert_eq!(builder.head().get_query(), "per_page=100");
+///             })
+///         }
+///     }
+/// }
+///
+/// # fn main() {
+/// let adapter = anterofit::Adapter::builder().build();
+/// let _ = adapter.repos("octocat");
+/// # }
+/// ```
+///
 /// ##Generics and `where` clauses
 /// Both of these are supported; however, the Rust grammar must be changed slightly
 /// so that they can be parsed and transformed properly by the `service!{}` macro without
@@ -246,6 +348,16 @@ macro_rules! unsizeable(
 #[doc(hidden)]
 #[macro_export]
 macro_rules! method_proto(
+    // Default steps are only used by `method_impl!{}`
+    (
+        defaults {
+            $($defaults:tt)*
+        }
+
+        $($rem:tt)*
+    ) => (
+        method_proto!($($rem)*);
+    );
     // Plain declaration
     (
         $(#[$fnmeta:meta])*
@@ -309,9 +421,21 @@ macro_rules! method_proto(
 #[doc(hidden)]
 #[macro_export]
 macro_rules! method_impl(
+    // Default steps for the following methods
+    (
+        $getadapt:expr; [$($_defaults:tt)*]
+
+        defaults {
+            $($defaults:tt)*
+        }
+
+        $($rem:tt)*
+    ) => (
+        method_impl!($getadapt; [$($defaults)*] $($rem)*);
+    );
     // Plain declaration
     (
-        $getadapt:expr;
+        $getadapt:expr; [$($defaults:tt)*]
 
         $(#[$fnmeta:meta])*
         fn $fnname:ident (&self $($args:tt)*) $(-> $ret:ty)* {
@@ -322,16 +446,16 @@ macro_rules! method_impl(
     ) => (
         $(#[$fnmeta])*
         fn $fnname (&self $($args)*)  -> $crate::net::Request<$($ret)*> {
-            request_impl! {
-                $crate::get_adapter(self, $getadapt); $($body)+
+            request_params! {
+                [] [$($args)*] { $crate::get_adapter(self, $getadapt); [$($defaults)*] $($body)+ }
             }
         }
         
-        method_impl!($getadapt; $($rem)*);
+        method_impl!($getadapt; [$($defaults)*] $($rem)*);
     );
     // Generics
     (
-        $getadapt:expr;
+        $getadapt:expr; [$($defaults:tt)*]
 
         $(#[$fnmeta:meta])*
         fn $fnname:ident [$($generics:tt)+] (&self $($args:tt)*) $(-> $ret:ty)* {
@@ -342,16 +466,16 @@ macro_rules! method_impl(
     ) => (
         $(#[$fnmeta])*
         fn $fnname <$($generics)+> (&self $($args)*) -> $crate::net::Request<$($ret)*> {
-            request_impl! {
-                $crate::get_adapter(self, $getadapt); $($body)+
+            request_params! {
+                [] [$($args)*] { $crate::get_adapter(self, $getadapt); [$($defaults)*] $($body)+ }
             }
         }
         
-        method_impl!($getadapt; $($rem)*);
+        method_impl!($getadapt; [$($defaults)*] $($rem)*);
     );
     // Where clause
     (
-        $getadapt:expr;
+        $getadapt:expr; [$($defaults:tt)*]
 
         $(#[$fnmeta:meta])*
         fn $fnname:ident  (&self $($args:tt)*) $(-> $ret:ty)* [where $($wheres:tt)+] {
@@ -362,16 +486,16 @@ macro_rules! method_impl(
     ) => (
         $(#[$fnmeta])*
         fn $fnname (&self $($args)*) -> $crate::net::Request<$($ret)*> where $($wheres)+ {
-            request_impl! {
-                $crate::get_adapter(self, $getadapt); $($body)+
+            request_params! {
+                [] [$($args)*] { $crate::get_adapter(self, $getadapt); [$($defaults)*] $($body)+ }
             }
         }
         
-        method_impl!($getadapt; $($rem)*);
+        method_impl!($getadapt; [$($defaults)*] $($rem)*);
     );
     // Generics + Where clause
     (
-        $getadapt:expr;
+        $getadapt:expr; [$($defaults:tt)*]
 
         $(#[$fnmeta:meta])*
         fn $fnname:ident [$($generics:tt)+] (&self $($args:tt)*) $(-> $ret:ty)* [where $($wheres:tt)+] {
@@ -382,15 +506,15 @@ macro_rules! method_impl(
     ) => (
         $(#[$fnmeta])*
         fn $fnname <$($generics)+> (&self $($args)*) -> $crate::net::Request<$($ret)*> where $($wheres)+ {
-            request_impl! {
-                $crate::get_adapter(self, $getadapt); $($body)+
+            request_params! {
+                [] [$($args)*] { $crate::get_adapter(self, $getadapt); [$($defaults)*] $($body)+ }
             }
         }
         
-        method_impl!($getadapt; $($rem)*);
+        method_impl!($getadapt; [$($defaults)*] $($rem)*);
     );
     // Empty end-case for recursion
-    ($_getadapt:expr; ) => ();
+    ($_getadapt:expr; [$($_defaults:tt)*]) => ();
 );
 
 #[doc(hidden)]
@@ -405,7 +529,7 @@ macro_rules! delegate_impl {
         $($rem:tt)*
     ) => (
         impl $servicenm for $delegate {
-            method_impl!($getadapt; $($guts)*);
+            method_impl!($getadapt; [] $($guts)*);
         }
 
         delegate_impl!($servicenm; [$($guts)*] $($rem)*);
@@ -419,7 +543,7 @@ macro_rules! delegate_impl {
         $($rem:tt)*
     ) => (
         impl<$($decls)*> $servicenm for $delegate {
-            method_impl!($getadapt; $($guts)*);
+            method_impl!($getadapt; [] $($guts)*);
         }
 
         delegate_impl!($servicenm; [$($guts)*] $($rem)*);
@@ -433,7 +557,7 @@ macro_rules! delegate_impl {
         $($rem:tt)*
     ) => (
         impl $servicenm for $delegate where $($wheres)+ {
-            method_impl!($getadapt; $($guts)*);
+            method_impl!($getadapt; [] $($guts)*);
         }
 
         delegate_impl!($servicenm; [$($guts)*] $($rem)*);
@@ -447,7 +571,7 @@ macro_rules! delegate_impl {
         $($rem:tt)*
     ) => (
         impl<$($decls)*> $servicenm for $delegate where $($wheres)+ {
-            method_impl!($getadapt; $($guts)*);
+            method_impl!($getadapt; [] $($guts)*);
         }
 
         delegate_impl!($servicenm; [$($guts)*] $($rem)*);
@@ -17,6 +17,49 @@ macro_rules! try_request (
 #[macro_export]
 #[doc(hidden)]
 macro_rules! url (
+    // Named format arguments which are bare identifiers (`name = ident`) are percent-encoded;
+    // positional arguments and other expressions are formatted as-is.
+    (@args $urlstr:expr; [$($out:tt)*];) => (
+        format!($urlstr, $($out)*)
+    );
+    (@args $urlstr:expr; [$($out:tt)*]; $name:ident = $arg:ident) => (
+        url!(@args $urlstr; [$($out)* $name = $crate::net::PathSegment(&$arg),];)
+    );
+    (@args $urlstr:expr; [$($out:tt)*]; $name:ident = $arg:ident, $($rest:tt)*) => (
+        url!(@args $urlstr; [$($out)* $name = $crate::net::PathSegment(&$arg),]; $($rest)*)
+    );
+    (@args $urlstr:expr; [$($out:tt)*]; $name:ident = $arg:expr) => (
+        url!(@args $urlstr; [$($out)* $name = $arg,];)
+    );
+    (@args $urlstr:expr; [$($out:tt)*]; $name:ident = $arg:expr, $($rest:tt)*) => (
+        url!(@args $urlstr; [$($out)* $name = $arg,]; $($rest)*)
+    );
+    (@args $urlstr:expr; [$($out:tt)*]; $arg:expr) => (
+        url!(@args $urlstr; [$($out)* $arg,];)
+    );
+    (@args $urlstr:expr; [$($out:tt)*]; $arg:expr, $($rest:tt)*) => (
+        url!(@args $urlstr; [$($out)* $arg,]; $($rest)*)
+    );
+    // Named placeholders are captured from the enclosing scope; the `$param`s are shadowed
+    // within this block so that they are percent-encoded when substituted. Nothing else is
+    // evaluated here, so the shadowing can't affect other uses of the parameters.
+    ([$($param:ident)*] $urlstr:literal) => ({
+        $(
+            #[allow(unused_variables)]
+            let $param = $crate::net::PathSegment(&$param);
+        )*
+
+        format!($urlstr)
+    });
+    ([$($param:ident)*] $urlstr:expr) => (
+        $urlstr
+    );
+    ([$($param:ident)*] $urlstr:expr, $($fmt:tt)+) => (
+        url!(@args $urlstr; []; $($fmt)+)
+    );
+    ($urlstr:literal) => (
+        format!($urlstr)
+    );
     ($urlstr:expr) => (
         $urlstr
     );
@@ -28,13 +71,28 @@ macro_rules! url (
 #[macro_export]
 #[doc(hidden)]
 macro_rules! request_impl {
-    ($adapter:expr; $method:ident($($urlpart:tt)+) $(; $buildexpr:expr)*) => ({
+    ($adapter:expr; $method:ident($($urlpart:tt)+) $(; $buildexpr:expr)*) => (
+        request_impl!($adapter; [] [] $method($($urlpart)+) $(; $buildexpr)*)
+    );
+    ($adapter:expr; [$($param:ident)*] $method:ident($($urlpart:tt)+) $(; $buildexpr:expr)*) => (
+        request_impl!($adapter; [$($param)*] [] $method($($urlpart)+) $(; $buildexpr)*)
+    );
+    // `$param`s are the method parameters, which are percent-encoded when substituted into the URL.
+    // `$default`s are the service's default steps, applied before the method's own.
+    (
+        $adapter:expr; [$($param:ident)*] [$($default:expr);* $(;)*]
+        $method:ident($($urlpart:tt)+) $(; $buildexpr:expr)*
+    ) => ({
         use $crate::net::RequestBuilder;
 
         let builder = RequestBuilder::new(
-            $adapter, http_verb!($method), url!($($urlpart)+).into()
+            $adapter, http_verb!($method), url!([$($param)*] $($urlpart)+).into()
         );
 
+        $(
+            let builder = try_request!(builder.apply($default));
+        )*
+
         $(
             let builder = try_request!(builder.apply($buildexpr));
         )*
@@ -43,6 +101,27 @@ macro_rules! request_impl {
     })
 }
 
+/// Collect the names of a service method's parameters for `request_impl!{}`.
+///
+/// Parameters bound with patterns other than `name` or `mut name` (and any after them)
+/// are skipped.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! request_params {
+    ([$($params:ident)*] [, mut $name:ident : $ty:ty $(, $($rem:tt)*)?] $then:tt) => (
+        request_params!([$($params)* $name] [$(, $($rem)*)?] $then)
+    );
+    ([$($params:ident)*] [, $name:ident : $ty:ty $(, $($rem:tt)*)?] $then:tt) => (
+        request_params!([$($params)* $name] [$(, $($rem)*)?] $then)
+    );
+    ([$($params:ident)*] [$(,)*] { $adapter:expr; $($body:tt)+ }) => (
+        request_impl! { $adapter; [$($params)*] $($body)+ }
+    );
+    ([$($params:ident)*] [$($other:tt)+] $then:tt) => (
+        request_params!([$($params)*] [] $then)
+    );
+}
+
 /// Allows the inside expression to set a body on a request which doesn't regularly take one.
 ///
 /// ```rust
@@ -201,6 +280,10 @@ macro_rules! body_map {
 /// }
 /// ```
 ///
+/// For in-memory file fields, use `FileField::from_bytes()` as the value. For per-part headers,
+/// serialized parts, or `multipart/mixed` and `multipart/related` bodies, see
+/// [`net::multipart`](net/multipart/index.html).
+///
 /// In some server stacks (e.g. PHP), these would be called `POST` parameters.
 ///
 /// ## Overwrites Body
@@ -299,8 +382,56 @@ macro_rules! path (
 /// Can be invoked multiple times. Duplicate keys are left to be handled by the server.
 ///
 /// In some server stacks (e.g. PHP), these would be called `GET` parameters.
+///
+/// ##Serializing Structs
+/// With the `SERIALIZE:` contextual keyword, the query pairs are instead taken from a value that
+/// is `Serialize`, such as a struct with many optional parameters. `None` values are skipped.
+///
+/// Sequences repeat the key for each element by default; use `SERIALIZE(Comma):`,
+/// `SERIALIZE(Brackets):` or `SERIALIZE(Indexed):` for other conventions (see
+/// [`serialize::form::Sequences`](serialize/form/enum.Sequences.html)).
+///
+/// ```rust
+/// # #[macro_use] extern crate anterofit;
+/// # #[macro_use] extern crate serde_derive;
+/// #[derive(Serialize)]
+/// pub struct SearchParams {
+///     q: String,
+///     language: Option<String>,
+///     tags: Vec<String>,
+/// }
+///
+/// service! {
+///     pub trait SearchService {
+///         fn search(&self, params: &SearchParams) -> Vec<String> {
+///             GET("/search");
+///             query!(SERIALIZE(Comma): params);
+///             with_builder!(|builder| {
+///                 assert_eq!(builder.head().get_query(), "q=serde&tags=rust%2Cjson")
+///             })
+///         }
+///     }
+/// }
+///
+/// # fn main() {
+/// let params = SearchParams {
+///     q: "serde".into(),
+///     language: None,
+///     tags: vec!["rust".into(), "json".into()],
+/// };
+///
+/// let adapter = anterofit::Adapter::builder().build();
+/// let _ = adapter.search(&params);
+/// # }
+/// ```
 #[macro_export]
 macro_rules! query {
+    (SERIALIZE: $query:expr) => (
+        query!(SERIALIZE(Repeat): $query)
+    );
+    (SERIALIZE($sequences:ident): $query:expr) => (
+        |builder| builder.serialize_query(&$query, $crate::serialize::form::Sequences::$sequences)
+    );
     ($($key:expr => $val:expr),+) => (
         |mut builder| {
             builder.head_mut().query(&[
@@ -311,6 +442,191 @@ macro_rules! query {
     )
 }
 
+/// Prepend a path to the URL of the request, such as a common prefix declared in
+/// a service's `defaults` block.
+///
+/// `$prefix` can be `&'static str` or `String`; it will not be percent-encoded.
+///
+/// ```rust
+/// # #[macro_use] extern crate anterofit;
+/// # fn main() {}
+/// service! {
+///     pub trait VersionedService {
+///         fn version(&self) -> String {
+///             GET("/version");
+///             // Requests `/api/v2/version`
+///             prefix!("/api/v2")
+///         }
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! prefix {
+    ($prefix:expr) => (
+        move |mut builder| {
+            builder.head_mut().prepend_url($prefix);
+            Ok(builder)
+        }
+    )
+}
+
+/// Set a series of HTTP headers on the request by name.
+///
+/// `$name` should be a string (`&'static str`, `String` or `Cow<'static, str>`) and `$val` can be
+/// anything that is `Display`; neither `Send` nor `'static` is required for the value.
+///
+/// Setting a header that was already set will overwrite the previous value.
+///
+/// ```rust
+/// # #[macro_use] extern crate anterofit;
+/// # fn main() {}
+/// service! {
+///     pub trait TraceService {
+///         fn traced(&self, trace_id: &str) {
+///             GET("/traced");
+///             headers! { "X-Trace" => trace_id }
+///         }
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! headers {
+    ($($name:expr => $val:expr),+) => (
+        |mut builder| {
+            $(
+                builder.head_mut().raw_header($name, &$val);
+            )+
+            Ok(builder)
+        }
+    );
+    ($($name:expr => $val:expr),+,) => (
+        headers!($($name => $val),+)
+    );
+}
+
+/// Serialize the request body with the given `Serializer` instead of the adapter's serializer.
+///
+/// The serializer also determines the `Content-Type` header sent with the body.
+///
+/// When using `body!(EAGER: ..)`, this must come first as the body is serialized immediately.
+///
+/// ```rust
+/// # #[macro_use] extern crate anterofit;
+/// # fn main() {}
+/// use anterofit::serialize::json;
+///
+/// service! {
+///     pub trait ReportService {
+///         fn submit_report(&self, report: Vec<String>) {
+///             POST("/reports");
+///             serializer!(json::PrettySerializer);
+///             body!(report)
+///         }
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! serializer {
+    ($serializer:expr) => (
+        move |builder| Ok(builder.serializer($serializer))
+    )
+}
+
+/// Deserialize the response with the given `Deserializer` instead of the adapter's deserializer.
+///
+/// ```rust
+/// # #[macro_use] extern crate anterofit;
+/// # fn main() {}
+/// use anterofit::serialize::FromStrDeserializer;
+///
+/// service! {
+///     pub trait MotdService {
+///         // The server returns the message as plain text.
+///         fn motd(&self) -> String {
+///             GET("/motd");
+///             deserializer!(FromStrDeserializer)
+///         }
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! deserializer {
+    ($deserializer:expr) => (
+        move |builder| Ok(builder.deserializer($deserializer))
+    )
+}
+
+/// Report the progress of uploading the request body or downloading the response body.
+///
+/// The value must be a `net::progress::OnProgress`. Download progress is reported as the response
+/// body is read, for any return type; see [`net::progress`](net/progress/index.html).
+///
+/// ```rust
+/// # #[macro_use] extern crate anterofit;
+/// # fn main() {}
+/// use anterofit::RawBody;
+/// use anterofit::net::progress::OnProgress;
+/// use std::fs::File;
+///
+/// service! {
+///     pub trait BackupService {
+///         fn backup(&self, archive: File, progress: OnProgress) {
+///             PUT("/backup");
+///             body!(RawBody::new(archive, None));
+///             progress!(upload: progress)
+///         }
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! progress {
+    (upload: $callback:expr) => (
+        move |mut builder| {
+            builder.head_mut().upload_progress($callback);
+            Ok(builder)
+        }
+    );
+    (download: $callback:expr) => (
+        move |mut builder| {
+            builder.head_mut().download_progress($callback);
+            Ok(builder)
+        }
+    );
+}
+
+/// Request a `text/event-stream` response, optionally resuming after the given `Last-Event-ID`.
+///
+/// `$last_event_id` must be an `Option` of a string type. Return `net::sse::EventStream` from the
+/// service method to iterate over the events; see [`net::sse`](net/sse/index.html).
+///
+/// ```rust
+/// # #[macro_use] extern crate anterofit;
+/// # fn main() {}
+/// use anterofit::net::sse::EventStream;
+///
+/// service! {
+///     pub trait CompletionService {
+///         fn complete(&self, prompt: &str) -> EventStream {
+///             POST("/complete");
+///             fields! { prompt };
+///             event_stream!()
+///         }
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! event_stream {
+    () => (
+        event_stream!(None::<&str>)
+    );
+    ($last_event_id:expr) => (
+        move |mut builder| {
+            $crate::net::sse::prepare_request(builder.head_mut(), $last_event_id);
+            Ok(builder)
+        }
+    );
+}
+
 /// Use in a service method body to perform an arbitrary transformation on the builder.
 ///
 /// ```rust
@@ -446,4 +762,5 @@ macro_rules! http_verb {
     (PUT) => ($crate::net::method::Put);
     (PATCH) => ($crate::net::method::Patch);
     (DELETE) => ($crate::net::method::Delete);
+    (HEAD) => ($crate::net::method::Head);
 }
\ No newline at end of file
@@ -25,4 +25,34 @@ pub fn formdata(boundary: &str) -> Mime {
 /// `text/plain; charset=utf8`
 pub fn text_plain_utf8() -> Mime {
     mime!(Text/Plain; Charset=Utf8)
-}
\ No newline at end of file
+}
+
+/// `application/msgpack`
+pub fn msgpack() -> Mime {
+    mime!(Application/("msgpack"))
+}
+
+/// `application/cbor`
+pub fn cbor() -> Mime {
+    mime!(Application/("cbor"))
+}
+
+/// `application/x-protobuf`
+pub fn protobuf() -> Mime {
+    mime!(Application/("x-protobuf"))
+}
+
+/// `application/yaml`
+pub fn yaml() -> Mime {
+    mime!(Application/("yaml"))
+}
+
+/// `application/toml`
+pub fn toml() -> Mime {
+    mime!(Application/("toml"))
+}
+
+/// `text/event-stream`
+pub fn event_stream() -> Mime {
+    mime!(Text/EventStream)
+}
@@ -1,26 +1,56 @@
-use crossbeam::sync::SegQueue;
 use parking_lot::{Condvar, Mutex};
 
+use std::cmp::Reverse;
+use std::collections::VecDeque;
 use std::iter::IntoIterator;
 
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use executor::ExecBox;
+use executor::{ExecBox, OverflowPolicy, Priority, QueueStats, RecvTimeoutError, ShutdownReport};
+
+use ::{Error, Result};
+
+/// The number of higher-priority jobs which may be dequeued ahead of a waiting job before its
+/// effective priority rises by one level.
+///
+/// This prevents starvation of low-priority jobs: every time a job is passed over, its effective
+/// priority rises until it eventually outranks anything newly submitted.
+const AGING_INTERVAL: u64 = 32;
 
 pub fn channel() -> (Sender, Receiver) {
+    channel_with(None)
+}
+
+/// Create a job queue which holds at most `capacity` jobs at a time, applying `policy`
+/// when a job is sent to a full queue.
+pub fn bounded(capacity: usize, policy: OverflowPolicy) -> (Sender, Receiver) {
+    channel_with(Some((capacity, policy)))
+}
+
+fn channel_with(bound: Option<(usize, OverflowPolicy)>) -> (Sender, Receiver) {
     let inner = Arc::new(
         Inner {
-            queue: SegQueue::new(),
-            mutex: Mutex::new(()),
+            queue: Mutex::new(Queue::new()),
             cvar: Condvar::new(),
-            closed: AtomicBool::new(false)
+            not_full: Condvar::new(),
+            drained: Condvar::new(),
+            bound: bound,
+            closed: AtomicBool::new(false),
+            receivers: AtomicUsize::new(1),
+            active: AtomicUsize::new(0),
+            waiting: AtomicUsize::new(0),
+            top: AtomicUsize::new(0),
+            locals: Mutex::new(Vec::new()),
+            held: AtomicUsize::new(0),
+            on_backlog: Mutex::new(None),
         }
     );
 
     let inner_ = inner.clone();
 
-    (Sender(inner), Receiver(inner_))
+    (Sender(inner), Receiver::new(inner_))
 }
 
 pub struct Sender(Arc<Inner>);
@@ -28,67 +58,563 @@ pub struct Sender(Arc<Inner>);
 /// The receiver half of an MPMC queue of executor jobs.
 ///
 /// Poll with `recv()`, when it returns `None` the job queue is closed.
-pub struct Receiver(Arc<Inner>);
+pub struct Receiver {
+    inner: Arc<Inner>,
+    // Jobs taken from the queue by `try_recv_batch()` which have not started yet.
+    local: Arc<Local>,
+    // Set while the job last returned by `recv()` is (presumably) being executed.
+    busy: AtomicBool,
+}
+
+type Local = Mutex<VecDeque<Entry>>;
 
 struct Inner {
-    queue: SegQueue<Box<ExecBox>>,
-    mutex: Mutex<()>,
+    queue: Mutex<Queue>,
     cvar: Condvar,
+    not_full: Condvar,
+    drained: Condvar,
+    bound: Option<(usize, OverflowPolicy)>,
     closed: AtomicBool,
+    receivers: AtomicUsize,
+    active: AtomicUsize,
+    // Receivers blocked waiting for a job; only modified with `queue` locked.
+    waiting: AtomicUsize,
+    // One more than the highest effective priority level in `queue`, or 0 if it is empty;
+    // only modified with `queue` locked.
+    top: AtomicUsize,
+    // The local queues of all receivers; locked after `queue`, if at all.
+    locals: Mutex<Vec<Arc<Local>>>,
+    // The number of jobs in local queues; only increased with `queue` locked.
+    held: AtomicUsize,
+    on_backlog: Mutex<Option<Arc<Fn() + Send + Sync>>>,
 }
 
 impl Sender {
-    pub fn send(&self, exec: Box<ExecBox>) {
-        self.0.queue.push(exec);
+    /// Push a job to the queue with the given priority, applying the overflow policy if it is
+    /// full.
+    ///
+    /// Returns `Error::QueueFull` if the queue was full and the policy is `Reject`,
+    /// or `Error::Shutdown` if `shutdown()` was called.
+    pub fn send(&self, exec: Box<ExecBox>, priority: Priority) -> Result<()> {
+        let mut queue = self.0.queue.lock();
+
+        if let Some((capacity, policy)) = self.0.bound {
+            while queue.len() >= capacity && !self.0.is_closed() {
+                match policy {
+                    OverflowPolicy::Block => self.0.not_full.wait(&mut queue),
+                    OverflowPolicy::Reject => return Err(Error::QueueFull),
+                    OverflowPolicy::DropOldest => {
+                        queue.drop_oldest();
+                        self.0.set_top(&queue);
+                    },
+                }
+            }
+        }
+
+        if self.0.is_closed() {
+            return Err(Error::Shutdown);
+        }
+
+        queue.push(exec, priority);
+        self.0.set_top(&queue);
+        self.0.cvar.notify_one();
+
+        let backlogged = self.0.waiting.load(Ordering::Relaxed) == 0;
+
+        drop(queue);
+
+        if backlogged {
+            self.0.backlogged();
+        }
+
+        Ok(())
+    }
+
+    /// Stop accepting new jobs and wait up to `timeout` for the receivers to finish the jobs
+    /// remaining in the queue and hang up.
+    ///
+    /// Jobs still in the queue when the timeout elapses are dropped.
+    pub fn shutdown(&self, timeout: Duration) -> ShutdownReport {
+        let deadline = Instant::now() + timeout;
+
+        self.0.closed.store(true, Ordering::Release);
+        self.0.clear_backlog();
+
+        let mut queue = self.0.queue.lock();
+
         self.0.cvar.notify_all();
+        // Wake any senders blocked on a full queue so they can observe the closed status
+        self.0.not_full.notify_all();
+
+        while self.0.receivers.load(Ordering::Acquire) > 0 {
+            if self.0.drained.wait_until(&mut queue, deadline).timed_out() {
+                break;
+            }
+        }
+
+        let mut abandoned = queue.drain();
+        self.0.set_top(&queue);
+
+        for local in self.0.locals.lock().iter() {
+            let mut local = local.lock();
+            self.0.held.fetch_sub(local.len(), Ordering::Relaxed);
+            abandoned.extend(local.drain(..).map(|entry| entry.job));
+        }
+
+        // Drop the jobs outside the lock as dropping completes their `Call`s
+        drop(queue);
+
+        ShutdownReport {
+            abandoned: abandoned.len(),
+            in_flight: self.0.active.load(Ordering::Relaxed),
+            workers: self.0.receivers.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Get a snapshot of the queue's current state.
+    pub fn stats(&self) -> QueueStats {
+        self.0.stats()
     }
 }
 
 impl Drop for Sender {
     fn drop(&mut self) {
         self.0.closed.store(true, Ordering::Release);
+        self.0.clear_backlog();
+
+        // Lock the queue so a receiver can't miss the notification between checking the flag
+        // and waiting.
+        let _queue = self.0.queue.lock();
         self.0.cvar.notify_all();
     }
 }
 
+impl Inner {
+    fn clear_backlog(&self) {
+        // The callback may own a `Receiver` which locks the queue when dropped,
+        // so it must not be dropped with the lock held.
+        let on_backlog = self.on_backlog.lock().take();
+        drop(on_backlog);
+    }
+
+    /// Invoke the backlog callback, if set. Must not be called with `queue` locked.
+    fn backlogged(&self) {
+        // Clone the hook so it isn't called with the lock held
+        let on_backlog = self.on_backlog.lock().clone();
+
+        if let Some(on_backlog) = on_backlog {
+            on_backlog();
+        }
+    }
+
+    /// Update `top` after `queue` was modified.
+    fn set_top(&self, queue: &Queue) {
+        let top = match queue.peek() {
+            Some((_, (level, _))) => level as usize + 1,
+            None => 0,
+        };
+
+        self.top.store(top, Ordering::Release);
+    }
+
+    fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    fn stats(&self) -> QueueStats {
+        QueueStats {
+            queued: self.queue.lock().len() + self.held.load(Ordering::Relaxed),
+            capacity: self.bound.map(|(capacity, _)| capacity),
+            receivers: self.receivers.load(Ordering::Relaxed),
+            active: self.active.load(Ordering::Relaxed),
+        }
+    }
+}
+
 impl Receiver {
-    fn wait(&self) {
-        // RFC: should this have a timeout?
-        self.0.cvar.wait(&mut self.0.mutex.lock());
+    fn new(inner: Arc<Inner>) -> Self {
+        let local = Arc::new(Mutex::new(VecDeque::new()));
+        inner.locals.lock().push(local.clone());
+
+        Receiver {
+            inner: inner,
+            local: local,
+            busy: AtomicBool::new(false),
+        }
     }
 
     /// Poll the queue, blocking if it is empty.
     ///
     /// Returns `None` when the sending half of the queue is closed.
     pub fn recv(&self) -> Option<Box<ExecBox>> {
+        self.recv_by(None).ok()
+    }
+
+    /// Poll the queue, blocking for at most `timeout` if it is empty.
+    pub fn recv_timeout(&self, timeout: Duration) -> ::std::result::Result<Box<ExecBox>, RecvTimeoutError> {
+        self.recv_by(Some(Instant::now() + timeout))
+    }
+
+    /// Poll the queue without blocking.
+    ///
+    /// Returns `None` if the queue is empty or closed.
+    pub fn try_recv(&self) -> Option<Box<ExecBox>> {
+        self.try_recv_batch(1)
+    }
+
+    /// Poll the queue without blocking, taking up to `max` jobs at once.
+    ///
+    /// The jobs after the first are moved to this receiver's local queue. Later calls to this
+    /// or the other `recv` methods take them from there without locking the shared queue,
+    /// unless a job of higher priority is waiting in it. Other receivers steal jobs from the
+    /// local queue before they start a younger job of equal or lower priority, so batched jobs
+    /// are not held up behind a long-running one.
+    ///
+    /// Jobs are only batched while no receiver is waiting for one and the queue is unbounded,
+    /// so a bounded queue still applies its capacity to every job which has not started.
+    /// Batched jobs are counted in `QueueStats::queued` and `ShutdownReport::abandoned`, and are
+    /// returned to the shared queue if this receiver is dropped.
+    ///
+    /// Returns `None` if the queue is empty or closed.
+    pub fn try_recv_batch(&self, max: usize) -> Option<Box<ExecBox>> {
+        if let Some(job) = self.pop_local() {
+            return Some(job);
+        }
+
+        let mut queue = self.inner.queue.lock();
+
+        let job = self.pop(&mut queue);
+        let batched = job.is_some() && max > 1 && self.batch(&mut queue, max - 1);
+
+        drop(queue);
+
+        // Give the executor a chance to start another worker to steal the batch
+        if batched {
+            self.inner.backlogged();
+        }
+
+        job
+    }
+
+    /// Set a callback to be invoked when a job is queued or batched by `try_recv_batch()` while
+    /// no receiver is waiting for one, i.e. all workers are busy.
+    ///
+    /// Executors can use this to start more workers under load. The callback should be cheap,
+    /// as it may be called on every request submission. Overwrites any previous callback.
+    pub fn on_backlog<F>(&self, on_backlog: F) where F: Fn() + Send + Sync + 'static {
+        *self.inner.on_backlog.lock() = Some(Arc::new(on_backlog));
+    }
+
+    fn recv_by(&self, deadline: Option<Instant>) -> ::std::result::Result<Box<ExecBox>, RecvTimeoutError> {
+        self.set_idle();
+
+        if let Some(job) = self.pop_local() {
+            return Ok(job);
+        }
+
+        let mut queue = self.inner.queue.lock();
+
         loop {
-            if let Some(val) = self.0.queue.try_pop() {
-                // Wake another thread so it can check if there's more work in the queue
-                self.0.cvar.notify_one();
-                return Some(val);
+            if let Some(val) = self.pop(&mut queue) {
+                return Ok(val);
             }
 
-            if self.0.closed.load(Ordering::Acquire) {
-                // Wake any remaining blocked threads so they can observe the closed status
-                self.0.cvar.notify_all();
-                return None;
+            if self.inner.is_closed() {
+                return Err(RecvTimeoutError::Closed);
             }
 
-            self.wait();
+            self.inner.waiting.fetch_add(1, Ordering::Relaxed);
+
+            let timed_out = match deadline {
+                Some(deadline) => self.inner.cvar.wait_until(&mut queue, deadline).timed_out(),
+                None => { self.inner.cvar.wait(&mut queue); false },
+            };
+
+            self.inner.waiting.fetch_sub(1, Ordering::Relaxed);
+
+            if timed_out {
+                return self.pop(&mut queue).ok_or(RecvTimeoutError::Timeout);
+            }
         }
     }
 
+    /// Take the next job from this receiver's local queue without locking the shared queue,
+    /// if the shared queue doesn't hold a job of higher priority.
+    fn pop_local(&self) -> Option<Box<ExecBox>> {
+        let top = self.inner.top.load(Ordering::Acquire);
+
+        let entry = {
+            let mut local = self.local.lock();
+
+            match local.front() {
+                Some(front) if front.level + 1 >= top => local.pop_front(),
+                _ => None,
+            }
+        };
+
+        entry.map(|entry| {
+            self.inner.held.fetch_sub(1, Ordering::Relaxed);
+            self.set_busy();
+            entry.job
+        })
+    }
+
+    /// Take the next job from the shared queue or any local queue, whichever is first
+    /// in priority order.
+    fn pop(&self, queue: &mut Queue) -> Option<Box<ExecBox>> {
+        let entry = match self.pop_held(queue) {
+            Some(entry) => entry,
+            None => match queue.pop() {
+                Some(entry) => {
+                    self.inner.set_top(queue);
+
+                    if self.inner.bound.is_some() {
+                        self.inner.not_full.notify_one();
+                    }
+
+                    entry
+                },
+                None => return None,
+            },
+        };
+
+        self.set_busy();
+        Some(entry.job)
+    }
+
+    /// Take the job at the front of a local queue if it outranks the next job in the shared
+    /// queue, stealing it from another receiver if necessary.
+    fn pop_held(&self, queue: &Queue) -> Option<Entry> {
+        // Only increased with `queue` locked, so this can't miss a batch
+        if self.inner.held.load(Ordering::Relaxed) == 0 {
+            return None;
+        }
+
+        let locals = self.inner.locals.lock();
+
+        let mut next = queue.peek().map(|(_, key)| key);
+        let mut from = None;
+
+        for local in locals.iter() {
+            if let Some(front) = local.lock().front() {
+                let key = queue.key(front);
+
+                match next {
+                    Some(next_key) if next_key >= key => (),
+                    _ => {
+                        next = Some(key);
+                        from = Some(local);
+                    },
+                }
+            }
+        }
+
+        // The owner of the local queue may have taken the job in the meantime
+        let entry = from.and_then(|local| local.lock().pop_front());
+
+        if entry.is_some() {
+            self.inner.held.fetch_sub(1, Ordering::Relaxed);
+        }
+
+        entry
+    }
+
+    /// Move up to `max` more jobs from the shared queue to this receiver's local queue,
+    /// returning `true` if any were moved.
+    fn batch(&self, queue: &mut Queue, max: usize) -> bool {
+        if self.inner.bound.is_some() || self.inner.waiting.load(Ordering::Relaxed) > 0 {
+            return false;
+        }
+
+        let mut local = self.local.lock();
+
+        // Batching behind jobs of lower priority would reorder them
+        if !local.is_empty() {
+            return false;
+        }
+
+        while local.len() < max {
+            match queue.pop() {
+                Some(entry) => local.push_back(entry),
+                None => break,
+            }
+        }
+
+        self.inner.held.fetch_add(local.len(), Ordering::Relaxed);
+        self.inner.set_top(queue);
+
+        !local.is_empty()
+    }
+
     /// Get a blocking iterator that yields `None` when the queue is closed.
     ///
     /// `IntoIter` is also implemented for `&Receiver`.
     pub fn iter(&self) -> RecvIter {
         RecvIter(self)
     }
+
+    /// Get a snapshot of the queue's current state.
+    pub fn stats(&self) -> QueueStats {
+        self.inner.stats()
+    }
+
+    fn set_busy(&self) {
+        if !self.busy.swap(true, Ordering::Relaxed) {
+            self.inner.active.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn set_idle(&self) {
+        if self.busy.swap(false, Ordering::Relaxed) {
+            self.inner.active.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// A priority queue of jobs, ordered by priority and then by submission order.
+///
+/// Each priority has its own FIFO queue; the fronts of these are compared on every pop,
+/// after aging.
+struct Queue {
+    levels: [VecDeque<Entry>; LEVELS],
+    // The number of jobs dequeued from each level
+    dequeued: [u64; LEVELS],
+    next_seq: u64,
+}
+
+const LEVELS: usize = 3;
+
+type Key = (u64, Reverse<u64>);
+
+struct Entry {
+    seq: u64,
+    level: usize,
+    // `passed()` when this job was queued
+    passed: u64,
+    job: Box<ExecBox>,
+}
+
+impl Queue {
+    fn new() -> Self {
+        Queue {
+            levels: [VecDeque::new(), VecDeque::new(), VecDeque::new()],
+            dequeued: [0; LEVELS],
+            next_seq: 0,
+        }
+    }
+
+    fn push(&mut self, job: Box<ExecBox>, priority: Priority) {
+        let level = priority.level() as usize;
+        let seq = self.next_seq;
+        self.next_seq += 1;
+
+        let passed = self.passed(level);
+
+        self.levels[level].push_back(Entry {
+            seq: seq,
+            level: level,
+            passed: passed,
+            job: job,
+        });
+    }
+
+    /// The effective priority and age of `entry`; the greatest key is popped first.
+    fn key(&self, entry: &Entry) -> Key {
+        // Every `AGING_INTERVAL` jobs of higher priorities dequeued while this job was
+        // waiting raise its effective priority by one level
+        let aged = (self.passed(entry.level) - entry.passed) / AGING_INTERVAL;
+        (entry.level as u64 + aged, Reverse(entry.seq))
+    }
+
+    /// The level and key of the job `pop()` would return.
+    fn peek(&self) -> Option<(usize, Key)> {
+        let mut next: Option<(usize, Key)> = None;
+
+        for level in 0 .. LEVELS {
+            if let Some(front) = self.levels[level].front() {
+                let key = self.key(front);
+
+                match next {
+                    Some((_, next_key)) if next_key >= key => (),
+                    _ => next = Some((level, key)),
+                }
+            }
+        }
+
+        next
+    }
+
+    fn pop(&mut self) -> Option<Entry> {
+        let level = match self.peek() {
+            Some((level, _)) => level,
+            None => return None,
+        };
+
+        self.dequeued[level] += 1;
+        self.levels[level].pop_front()
+    }
+
+    /// Return entries taken by `pop()`, in the order they were taken, to the front of
+    /// their levels.
+    fn restore<I>(&mut self, entries: I) where I: DoubleEndedIterator<Item = Entry> {
+        for entry in entries.rev() {
+            self.levels[entry.level].push_front(entry);
+        }
+    }
+
+    /// The number of jobs of higher priority than `level` which have been dequeued.
+    fn passed(&self, level: usize) -> u64 {
+        self.dequeued[level + 1 ..].iter().sum()
+    }
+
+    fn drop_oldest(&mut self) {
+        let oldest = (0 .. LEVELS)
+            .filter_map(|level| self.levels[level].front().map(|front| (front.seq, level)))
+            .min();
+
+        if let Some((_, level)) = oldest {
+            // Dropping the job completes its `Call` with `Error::Abandoned`.
+            self.levels[level].pop_front();
+        }
+    }
+
+    fn drain(&mut self) -> Vec<Box<ExecBox>> {
+        self.levels.iter_mut().flat_map(|level| level.drain(..)).map(|entry| entry.job).collect()
+    }
+
+    fn len(&self) -> usize {
+        self.levels.iter().map(VecDeque::len).sum()
+    }
 }
 
 impl Clone for Receiver {
     fn clone(&self) -> Self {
-        Receiver(self.0.clone())
+        self.inner.receivers.fetch_add(1, Ordering::Relaxed);
+        Receiver::new(self.inner.clone())
+    }
+}
+
+impl Drop for Receiver {
+    fn drop(&mut self) {
+        self.set_idle();
+
+        // Lock the queue so `Sender::shutdown()` can't miss the notification.
+        let mut queue = self.inner.queue.lock();
+
+        self.inner.locals.lock().retain(|local| !Arc::ptr_eq(local, &self.local));
+
+        // Return batched jobs to the shared queue so other receivers can take them
+        let batched: Vec<_> = self.local.lock().drain(..).collect();
+
+        if !batched.is_empty() {
+            self.inner.held.fetch_sub(batched.len(), Ordering::Relaxed);
+            queue.restore(batched.into_iter());
+            self.inner.set_top(&queue);
+            self.inner.cvar.notify_all();
+        }
+
+        self.inner.receivers.fetch_sub(1, Ordering::Release);
+        self.inner.drained.notify_all();
     }
 }
 
@@ -131,3 +657,202 @@ impl<'a> Iterator for RecvIter<'a> {
         self.0.recv()
     }
 }
+
+#[test]
+fn bounded_queue_overflow() {
+    let (tx, rx) = bounded(1, OverflowPolicy::Reject);
+    tx.send(ExecBox::noop(), Priority::Normal).unwrap();
+
+    match tx.send(ExecBox::noop(), Priority::Normal) {
+        Err(Error::QueueFull) => (),
+        res => panic!("expected `Error::QueueFull`, got {:?}", res),
+    }
+
+    assert!(rx.recv().is_some());
+    assert_eq!(rx.stats().active, 1);
+    tx.send(ExecBox::noop(), Priority::Normal).unwrap();
+
+    let (tx, rx) = bounded(2, OverflowPolicy::DropOldest);
+
+    for _ in 0 .. 3 {
+        tx.send(ExecBox::noop(), Priority::Normal).unwrap();
+    }
+
+    assert_eq!(tx.stats().queued, 2);
+
+    drop(tx);
+
+    assert_eq!(rx.iter().count(), 2);
+    assert_eq!(rx.stats().active, 0);
+}
+
+#[test]
+fn shutdown_drains_queue() {
+    use std::thread;
+
+    let (tx, rx) = channel();
+
+    for _ in 0 .. 3 {
+        tx.send(ExecBox::noop(), Priority::Normal).unwrap();
+    }
+
+    let worker = thread::spawn(move || rx.iter().count());
+
+    let report = tx.shutdown(Duration::from_secs(10));
+    assert_eq!(report, ShutdownReport { abandoned: 0, in_flight: 0, workers: 0 });
+    assert_eq!(worker.join().unwrap(), 3);
+
+    match tx.send(ExecBox::noop(), Priority::Normal) {
+        Err(Error::Shutdown) => (),
+        res => panic!("expected `Error::Shutdown`, got {:?}", res),
+    }
+
+    let (tx, _rx) = channel();
+    tx.send(ExecBox::noop(), Priority::Normal).unwrap();
+
+    let report = tx.shutdown(Duration::from_millis(10));
+    assert_eq!(report.abandoned, 1);
+    assert!(!report.is_clean());
+}
+
+#[test]
+fn recv_timeout_and_backlog() {
+    let (tx, rx) = channel();
+
+    match rx.recv_timeout(Duration::from_millis(1)) {
+        Err(RecvTimeoutError::Timeout) => (),
+        _ => panic!("expected `RecvTimeoutError::Timeout`"),
+    }
+
+    let backlogged = Arc::new(AtomicUsize::new(0));
+    let backlogged_ = backlogged.clone();
+
+    rx.on_backlog(move || { backlogged_.fetch_add(1, Ordering::Relaxed); });
+
+    tx.send(ExecBox::noop(), Priority::Normal).unwrap();
+    assert_eq!(backlogged.load(Ordering::Relaxed), 1);
+
+    assert!(rx.try_recv().is_some());
+    assert!(rx.try_recv().is_none());
+
+    drop(tx);
+
+    match rx.recv_timeout(Duration::from_secs(10)) {
+        Err(RecvTimeoutError::Closed) => (),
+        _ => panic!("expected `RecvTimeoutError::Closed`"),
+    }
+}
+
+#[test]
+fn priority_order_and_aging() {
+    use std::sync::Mutex as StdMutex;
+
+    let (tx, rx) = channel();
+    let order = Arc::new(StdMutex::new(Vec::new()));
+
+    let send = |id: usize, priority: Priority| {
+        let order = order.clone();
+        tx.send(Box::new(move || order.lock().unwrap().push(id)), priority).unwrap();
+    };
+
+    send(0, Priority::Low);
+    send(1, Priority::Normal);
+    send(2, Priority::High);
+    send(3, Priority::Normal);
+
+    // Enough high-priority jobs to age the low-priority job to the front
+    for id in 4 .. 4 + 3 * AGING_INTERVAL as usize {
+        send(id, Priority::High);
+    }
+
+    while let Some(job) = rx.try_recv() {
+        job.exec();
+    }
+
+    let order = order.lock().unwrap();
+    assert_eq!(&order[..1], &[2]);
+
+    let low_pos = order.iter().position(|&id| id == 0).unwrap();
+    assert!(low_pos < order.len() - 1, "low-priority job was starved: {:?}", *order);
+    assert!(order.iter().position(|&id| id == 1) < order.iter().position(|&id| id == 3));
+}
+
+#[test]
+fn priority_jumps_large_backlog() {
+    let (tx, rx) = channel();
+
+    for _ in 0 .. 10 * AGING_INTERVAL {
+        tx.send(ExecBox::noop(), Priority::Low).unwrap();
+    }
+
+    // Jobs of the same priority don't age each other
+    for _ in 0 .. 3 * AGING_INTERVAL {
+        rx.try_recv().unwrap();
+    }
+
+    let (high, ran) = ::std::sync::mpsc::channel();
+    tx.send(Box::new(move || high.send(()).unwrap()), Priority::High).unwrap();
+
+    rx.try_recv().unwrap().exec();
+    assert!(ran.try_recv().is_ok(), "high-priority job waited behind the backlog");
+    assert_eq!(tx.stats().queued, 7 * AGING_INTERVAL as usize);
+}
+
+#[test]
+fn batch_and_steal() {
+    use std::sync::Mutex as StdMutex;
+
+    let (tx, rx) = channel();
+    let rx2 = rx.clone();
+    let order = Arc::new(StdMutex::new(Vec::new()));
+
+    let send = |id: usize, priority: Priority| {
+        let order = order.clone();
+        tx.send(Box::new(move || order.lock().unwrap().push(id)), priority).unwrap();
+    };
+
+    let run = |job: Option<Box<ExecBox>>| job.expect("expected a job").exec();
+
+    for id in 0 .. 4 {
+        send(id, Priority::Normal);
+    }
+
+    // Takes 0 and batches 1 and 2
+    run(rx.try_recv_batch(3));
+    assert_eq!(tx.stats().queued, 3);
+
+    // Steals 1, which is older than 3 in the shared queue
+    run(rx2.try_recv());
+
+    // 4 outranks the rest of the batch
+    send(4, Priority::High);
+    run(rx.try_recv_batch(3));
+    run(rx.try_recv_batch(3));
+    run(rx2.try_recv());
+
+    assert_eq!(*order.lock().unwrap(), [0, 1, 4, 2, 3]);
+
+    // Batched jobs are returned to the shared queue when their receiver is dropped
+    for id in 5 .. 8 {
+        send(id, Priority::Normal);
+    }
+
+    run(rx2.try_recv_batch(3));
+    drop(rx2);
+
+    assert_eq!(tx.stats().queued, 2);
+
+    while let Some(job) = rx.try_recv() {
+        job.exec();
+    }
+
+    assert_eq!(order.lock().unwrap()[5 ..], [5, 6, 7]);
+
+    // ...and counted as abandoned if they haven't started when the shutdown times out
+    for id in 8 .. 11 {
+        send(id, Priority::Normal);
+    }
+
+    run(rx.try_recv_batch(3));
+    assert_eq!(tx.shutdown(Duration::from_millis(1)).abandoned, 2);
+}
@@ -0,0 +1,363 @@
+//! Helpers for executing many requests concurrently.
+//!
+//! ```rust,no_run
+//! # #[macro_use] extern crate anterofit;
+//! # fn main() {
+//! use anterofit::Adapter;
+//! use anterofit::net::batch::Batch;
+//!
+//! service! {
+//!     trait UserService {
+//!         fn user_name(&self, id: u64) -> String {
+//!             GET("/users/{}/name", id)
+//!         }
+//!     }
+//! }
+//!
+//! let adapter = Adapter::builder().build();
+//!
+//! // Execute up to 8 lookups at a time, returning the names in the order of `ids`
+//! // or the first error that occurs.
+//! let names = Batch::new((0 .. 500).map(|id| adapter.user_name(id)))
+//!     .concurrency(8)
+//!     .exec()
+//!     .try_collect()
+//!     .unwrap();
+//! # }
+//! ```
+
+use futures::{executor, Async, Future, Poll, Stream};
+
+use std::collections::{BTreeMap, VecDeque};
+use std::iter::Fuse;
+
+use net::call::Call;
+use net::request::Request;
+
+use ::{Error, Result};
+
+/// The number of requests `Batch` will execute at once unless `concurrency()` is called.
+pub const DEFAULT_CONCURRENCY: usize = 16;
+
+/// A builder for executing a series of requests concurrently.
+///
+/// Call `exec()` to start executing the requests.
+#[derive(Debug)]
+pub struct Batch<I> {
+    requests: I,
+    concurrency: usize,
+    ordered: bool,
+    fail_fast: bool,
+}
+
+impl<'a, I, T> Batch<I> where I: Iterator<Item = Request<'a, T>>, T: Send + 'static {
+    /// Wrap a series of requests, such as an iterator mapping IDs to service method calls.
+    ///
+    /// The series is consumed lazily, so requests are not constructed until they are
+    /// about to be executed.
+    pub fn new<R>(requests: R) -> Self where R: IntoIterator<IntoIter = I, Item = Request<'a, T>> {
+        Batch {
+            requests: requests.into_iter(),
+            concurrency: DEFAULT_CONCURRENCY,
+            ordered: false,
+            fail_fast: false,
+        }
+    }
+
+    /// Set the maximum number of requests which will be queued on the executor at once.
+    ///
+    /// Defaults to `DEFAULT_CONCURRENCY`.
+    ///
+    /// ## Panics
+    /// If `concurrency` is zero.
+    pub fn concurrency(self, concurrency: usize) -> Self {
+        assert!(concurrency > 0, "Batch concurrency must be nonzero");
+        Batch { concurrency: concurrency, .. self }
+    }
+
+    /// Yield results in the order of the input series instead of the order they complete.
+    ///
+    /// Results which complete early are buffered until all results before them are yielded.
+    pub fn ordered(self) -> Self {
+        Batch { ordered: true, .. self }
+    }
+
+    /// Stop executing new requests after the first error, which will be the last result yielded.
+    ///
+    /// Requests which were already executing will complete in the background,
+    /// but their results are discarded.
+    pub fn fail_fast(self) -> Self {
+        Batch { fail_fast: true, .. self }
+    }
+
+    /// Begin executing requests, returning a handle to retrieve the results.
+    pub fn exec(self) -> Dispatch<I, T> {
+        let mut dispatch = Dispatch {
+            requests: self.requests.fuse(),
+            next_idx: 0,
+            concurrency: self.concurrency,
+            fail_fast: self.fail_fast,
+            errored: false,
+            failed: false,
+            in_flight: Vec::with_capacity(self.concurrency),
+            ready: if self.ordered {
+                Ready::Ordered { next_idx: 0, results: BTreeMap::new() }
+            } else {
+                Ready::Unordered(VecDeque::new())
+            },
+        };
+
+        dispatch.fill();
+
+        dispatch
+    }
+}
+
+/// A handle to a series of executing requests, returned by `Batch::exec()`.
+///
+/// Results are paired with the index of their request in the input series.
+///
+/// Implements `Iterator`, which blocks until the next result is available, and `Stream`,
+/// which yields errors as `(index, error)`.
+pub struct Dispatch<I, T> {
+    requests: Fuse<I>,
+    next_idx: usize,
+    concurrency: usize,
+    fail_fast: bool,
+    // An error was received with `fail_fast` set; no new requests will be executed.
+    errored: bool,
+    // The error was yielded; no more results will be yielded.
+    failed: bool,
+    in_flight: Vec<(usize, Call<T>)>,
+    ready: Ready<T>,
+}
+
+enum Ready<T> {
+    Ordered {
+        next_idx: usize,
+        results: BTreeMap<usize, Result<T>>,
+    },
+    Unordered(VecDeque<(usize, Result<T>)>),
+}
+
+impl<T> Ready<T> {
+    fn push(&mut self, idx: usize, res: Result<T>) {
+        match *self {
+            Ready::Ordered { ref mut results, .. } => { results.insert(idx, res); },
+            Ready::Unordered(ref mut results) => results.push_back((idx, res)),
+        }
+    }
+
+    fn pop(&mut self) -> Option<(usize, Result<T>)> {
+        match *self {
+            Ready::Ordered { ref mut next_idx, ref mut results } => {
+                let res = results.remove(next_idx);
+                res.map(|res| { *next_idx += 1; (*next_idx - 1, res) })
+            },
+            Ready::Unordered(ref mut results) => results.pop_front(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        match *self {
+            Ready::Ordered { ref results, .. } => results.is_empty(),
+            Ready::Unordered(ref results) => results.is_empty(),
+        }
+    }
+}
+
+impl<'a, I, T> Dispatch<I, T> where I: Iterator<Item = Request<'a, T>>, T: Send + 'static {
+    /// Block until all requests have completed, returning their results in input order.
+    ///
+    /// If `fail_fast()` was set, this will stop at the first error, and the requests after it
+    /// will not be included.
+    pub fn collect_all(self) -> Vec<Result<T>> {
+        let mut results: Vec<_> = Iterator::collect(self);
+        results.sort_by_key(|&(idx, _)| idx);
+        results.into_iter().map(|(_, res)| res).collect()
+    }
+
+    /// Block until all requests have completed, returning their values in input order,
+    /// or return the first error that occurs.
+    ///
+    /// No new requests will be executed after an error occurs, as with `fail_fast()`.
+    pub fn try_collect(mut self) -> Result<Vec<T>> {
+        self.fail_fast = true;
+
+        let mut values = Vec::new();
+
+        for (idx, res) in self {
+            values.push((idx, try!(res)));
+        }
+
+        values.sort_by_key(|&(idx, _)| idx);
+        Ok(values.into_iter().map(|(_, val)| val).collect())
+    }
+
+    /// Execute requests until `concurrency` are in-flight or the series is exhausted.
+    fn fill(&mut self) {
+        if self.errored { return; }
+
+        while self.in_flight.len() < self.concurrency {
+            let request = match self.requests.next() {
+                Some(request) => request,
+                None => return,
+            };
+
+            self.in_flight.push((self.next_idx, request.exec()));
+            self.next_idx += 1;
+        }
+    }
+
+    fn poll_next(&mut self) -> Async<Option<(usize, Result<T>)>> {
+        if self.failed {
+            return Async::Ready(None);
+        }
+
+        loop {
+            let mut completed = false;
+            let mut i = 0;
+
+            while i < self.in_flight.len() {
+                let res = match self.in_flight[i].1.poll() {
+                    Ok(Async::Ready(val)) => Ok(val),
+                    Ok(Async::NotReady) => { i += 1; continue; },
+                    Err(e) => Err(e),
+                };
+
+                if res.is_err() && self.fail_fast {
+                    self.errored = true;
+                }
+
+                let (idx, _) = self.in_flight.remove(i);
+                self.ready.push(idx, res);
+                completed = true;
+            }
+
+            if !completed { break; }
+
+            // Newly executed requests need to be polled so they notify the current task.
+            self.fill();
+        }
+
+        match self.ready.pop() {
+            Some((idx, res)) => {
+                if res.is_err() && self.fail_fast {
+                    self.failed = true;
+                }
+
+                Async::Ready(Some((idx, res)))
+            },
+            None if self.in_flight.is_empty() && self.ready.is_empty() => Async::Ready(None),
+            None => Async::NotReady,
+        }
+    }
+}
+
+impl<'a, I, T> Stream for Dispatch<I, T> where I: Iterator<Item = Request<'a, T>>, T: Send + 'static {
+    type Item = (usize, T);
+    type Error = (usize, Error);
+
+    /// ### Panics
+    /// If the current thread is not running a futures task.
+    fn poll(&mut self) -> Poll<Option<(usize, T)>, (usize, Error)> {
+        match self.poll_next() {
+            Async::Ready(Some((idx, Ok(val)))) => Ok(Async::Ready(Some((idx, val)))),
+            Async::Ready(Some((idx, Err(e)))) => Err((idx, e)),
+            Async::Ready(None) => Ok(Async::Ready(None)),
+            Async::NotReady => Ok(Async::NotReady),
+        }
+    }
+}
+
+impl<'a, I, T> Iterator for Dispatch<I, T> where I: Iterator<Item = Request<'a, T>>, T: Send + 'static {
+    type Item = (usize, Result<T>);
+
+    /// Block until the next result is available.
+    fn next(&mut self) -> Option<(usize, Result<T>)> {
+        match executor::spawn(self).wait_stream() {
+            Some(Ok((idx, val))) => Some((idx, Ok(val))),
+            Some(Err((idx, e))) => Some((idx, Err(e))),
+            None => None,
+        }
+    }
+}
+
+#[test]
+fn batch_ordering_and_errors() {
+    let requests = || vec![
+        Request::immediate(Ok(0)),
+        Request::immediate(Err(Error::QueueFull)),
+        Request::immediate(Ok(2)),
+    ];
+
+    let results = Batch::new(requests()).concurrency(1).ordered().exec().collect_all();
+    assert_eq!(results.len(), 3);
+    assert!(results[1].is_err());
+    assert_eq!(*results[2].as_ref().unwrap(), 2);
+
+    let results: Vec<_> = Iterator::collect(Batch::new(requests()).concurrency(1).fail_fast().exec());
+    assert_eq!(results.len(), 2);
+
+    assert!(Batch::new(requests()).exec().try_collect().is_err());
+}
+
+#[test]
+fn batch_on_executor() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    use adapter::Adapter;
+    use executor::threaded::MultiThread;
+    use net::method::Get;
+    use net::request::RequestBuilder;
+
+    const COUNT: usize = 12;
+
+    // Counts the requests started, running and the most running at once
+    type Counters = Arc<[AtomicUsize; 3]>;
+
+    // Without a base URL, these fail on the executor before any network activity;
+    // `on_result()` stands in for handling the response.
+    fn requests<'a>(adapter: &'a Adapter, counters: &Counters, fail_at: Option<usize>) -> Vec<Request<'a, usize>> {
+        (0 .. COUNT).map(|i| {
+            let counters = counters.clone();
+
+            RequestBuilder::new(adapter, Get, "".into()).build::<()>().on_result(move |_| {
+                counters[0].fetch_add(1, Ordering::AcqRel);
+                let running = counters[1].fetch_add(1, Ordering::AcqRel) + 1;
+                counters[2].fetch_max(running, Ordering::AcqRel);
+
+                // Later requests complete sooner
+                thread::sleep(Duration::from_millis(2 * (COUNT - i) as u64));
+                counters[1].fetch_sub(1, Ordering::AcqRel);
+
+                if Some(i) == fail_at { Err(Error::QueueFull) } else { Ok(i) }
+            })
+        }).collect()
+    }
+
+    let adapter = Adapter::builder().executor(MultiThread::new(4)).build();
+
+    let counters: Counters = Arc::new([AtomicUsize::new(0), AtomicUsize::new(0), AtomicUsize::new(0)]);
+    let results: Vec<_> = Iterator::collect(Batch::new(requests(&adapter, &counters, None)).concurrency(3).ordered().exec());
+
+    assert_eq!(results.iter().map(|&(idx, _)| idx).collect::<Vec<_>>(), (0 .. COUNT).collect::<Vec<_>>());
+    assert!(results.iter().all(|&(idx, ref res)| *res.as_ref().unwrap() == idx));
+    assert_eq!(counters[2].load(Ordering::Acquire), 3);
+
+    let counters: Counters = Arc::new([AtomicUsize::new(0), AtomicUsize::new(0), AtomicUsize::new(0)]);
+    let results: Vec<_> = Iterator::collect(Batch::new(requests(&adapter, &counters, Some(2))).concurrency(2).fail_fast().exec());
+
+    match results.last() {
+        Some(&(2, Err(_))) => (),
+        res => panic!("expected the error to be the last result, got {:?}", res),
+    }
+
+    // Let the requests which were already executing complete
+    assert!(adapter.shutdown(Duration::from_secs(10)).is_clean());
+    assert!(counters[0].load(Ordering::Acquire) < COUNT, "requests were executed after the error");
+    assert!(counters[2].load(Ordering::Acquire) <= 2);
+}
@@ -13,6 +13,7 @@ use url::form_urlencoded::Serializer as FormUrlEncoder;
 
 use std::borrow::Borrow;
 use std::fmt;
+use std::fs::File;
 use std::io::{self, Cursor, Read};
 use std::path::PathBuf;
 
@@ -28,6 +29,10 @@ pub struct Readable<R> {
     pub readable: R,
     /// The MIME type of the request body, if applicable.
     pub content_type: Option<Mime>,
+    /// The length of the request body in bytes, if known.
+    ///
+    /// If `None`, the body is sent with `Transfer-Encoding: chunked`.
+    pub content_length: Option<u64>,
     // Throwaway private field for backwards compatibility.
     _private: (),
 }
@@ -44,9 +49,26 @@ impl<R: Read> Readable<R> {
         Readable {
             readable: readable,
             content_type: content_type.into(),
+            content_length: None,
             _private: (),
         }
     }
+
+    /// Set the length of the request body in bytes, or `None` to send it with
+    /// `Transfer-Encoding: chunked`.
+    pub fn with_content_length<L: Into<Option<u64>>>(self, content_length: L) -> Self {
+        Readable { content_length: content_length.into(), .. self }
+    }
+}
+
+impl<T: AsRef<[u8]>> Readable<Cursor<T>> {
+    /// Create a new `Readable` from a `Cursor`, with the remaining length of the buffer as the
+    /// content length.
+    pub fn from_cursor<C: Into<Option<Mime>>>(cursor: Cursor<T>, content_type: C) -> Self {
+        let len = cursor.get_ref().as_ref().len() as u64;
+        let content_length = len.saturating_sub(cursor.position());
+        Readable::new(cursor, content_type).with_content_length(content_length)
+    }
 }
 
 /// A trait describing a type which can be serialized into a request body.
@@ -89,7 +111,7 @@ impl<B: Serialize> EagerBody for B {
 
         try!(ser.serialize(&self, &mut buf));
 
-        Readable::new_ok(Cursor::new(buf), ser.content_type())
+        Ok(Readable::from_cursor(Cursor::new(buf), ser.content_type()))
     }
 }
 
@@ -105,12 +127,30 @@ impl<R: Read> RawBody<R> {
     }
 }
 
+impl<R> RawBody<R> {
+    /// Set the length of the body in bytes, or `None` to send it with
+    /// `Transfer-Encoding: chunked`.
+    ///
+    /// Bodies created with `RawBody::new()` have no length unless one is set here.
+    pub fn with_content_length<L: Into<Option<u64>>>(self, content_length: L) -> Self {
+        RawBody(Readable { content_length: content_length.into(), .. self.0 })
+    }
+}
+
+impl RawBody<File> {
+    /// Wrap an open file as a raw request body, with its length as the content length.
+    pub fn file<C: Into<Option<Mime>>>(file: File, content_type: C) -> io::Result<Self> {
+        let len = try!(file.metadata()).len();
+        Ok(RawBody::new(file, content_type).with_content_length(len))
+    }
+}
+
 impl<T: AsRef<[u8]>> RawBody<Cursor<T>> {
     /// Wrap anything `Cursor` can work with (such as `String` or `Vec<u8>`) as a raw request body.
     ///
     /// Assumes `application/octet-stream` as the content-type.
     pub fn bytes(bytes: T) -> Self {
-        RawBody::new(Cursor::new(bytes), mime::octet_stream())
+        RawBody(Readable::from_cursor(Cursor::new(bytes), mime::octet_stream()))
     }
 
     /// Wrap anything `Send + 'static` that can deref to `str`
@@ -119,7 +159,7 @@ impl<T: AsRef<[u8]>> RawBody<Cursor<T>> {
     ///
     /// Assumes `text/plain; charset=utf8` as the content-type.
     pub fn text(text: T) -> Self where T: Borrow<str> {
-        RawBody::new(Cursor::new(text), mime::text_plain_utf8())
+        RawBody(Readable::from_cursor(Cursor::new(text), mime::text_plain_utf8()))
     }
 }
 
@@ -138,7 +178,7 @@ impl RawBody<Cursor<Vec<u8>>> {
     where S: Serializer, T: Serialize {
         let mut buf: Vec<u8> = Vec::new();
         try!(ser.serialize(val, &mut buf));
-        Ok(RawBody::new(Cursor::new(buf), ser.content_type()))
+        Ok(RawBody(Readable::from_cursor(Cursor::new(buf), ser.content_type())))
     }
 }
 
@@ -193,7 +233,7 @@ impl Body for EmptyFields {
 
     fn into_readable<S>(self, _ser: &S) -> ReadableResult<Self::Readable>
     where S: Serializer {
-        Readable::new_ok(io::empty(), None)
+        Ok(Readable::new(io::empty(), None).with_content_length(0))
     }
 }
 
@@ -236,7 +276,7 @@ impl Body for TextFields {
                 .finish()
         );
 
-        Readable::new_ok(readable, mime::form_urlencoded())
+        Ok(Readable::from_cursor(readable, mime::form_urlencoded()))
     }
 }
 
@@ -306,8 +346,9 @@ impl Body for MultipartFields {
         let prepared = try!(multipart.prepare());
 
         let content_type = mime::formdata(prepared.boundary());
+        let content_length = prepared.content_len();
 
-        Readable::new_ok(prepared, content_type)
+        Ok(Readable::new(prepared, content_type).with_content_length(content_length))
     }
 }
 
@@ -324,6 +365,11 @@ impl FileField {
         })
     }
 
+    /// Wrap in-memory bytes to be serialized as a file field with the given filename and MIME type.
+    pub fn from_bytes<B: Into<Vec<u8>>, F: Into<String>>(bytes: B, filename: F, content_type: Mime) -> Self {
+        Self::from_stream(Cursor::new(bytes.into()), Some(filename.into()), Some(content_type))
+    }
+
     /// Wrap a `Path` to be serialized as a file field, inferring its filename and MIME type.
     pub fn from_path<P: Into<PathBuf>>(path: P) -> Self {
         FileField(FileField_::Path(path.into()))
@@ -5,6 +5,7 @@ use ::{Result, Error};
 use std::mem;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::thread;
 
 use error::RequestPanicked;
 
@@ -158,7 +159,7 @@ pub fn immediate<T>(res: Result<T>) -> Call<T> {
     }
 }
 
-/// Sends the request head on panic.
+/// Sends the request head on panic, or `Error::Abandoned` if dropped without completing.
 pub struct PanicGuard<T> {
     head: Option<RequestHead>,
     tx: Option<Complete<Result<T>>>,
@@ -180,7 +181,10 @@ impl<T> PanicGuard<T> {
 
 impl<T> Drop for PanicGuard<T> {
     fn drop(&mut self) {
-        if let Some(head) = self.head.take() {
+        if !thread::panicking() {
+            // The job was dropped without being executed.
+            self.complete(Err(Error::Abandoned));
+        } else if let Some(head) = self.head.take() {
             self.complete(Err(RequestPanicked(head).into()));
         }
     }
@@ -0,0 +1,41 @@
+//! Opening connections outside of `hyper::Client`, for requests which upgrade the connection to
+//! another protocol.
+
+use hyper::net::{NetworkConnector, NetworkStream};
+
+use url::Url;
+
+use net::request::RequestHead;
+
+use ::Result;
+
+/// An object-safe version of `hyper::net::NetworkConnector`.
+///
+/// Set on the adapter with `AdapterBuilder::connector()`.
+pub trait Connect: Send + Sync + 'static {
+    /// Open a connection to `host` and `port`, where `scheme` is `http` or `https`.
+    fn connect(&self, host: &str, port: u16, scheme: &str) -> ::hyper::Result<Box<NetworkStream + Send>>;
+}
+
+impl<C> Connect for C where C: NetworkConnector + Send + Sync + 'static {
+    fn connect(&self, host: &str, port: u16, scheme: &str) -> ::hyper::Result<Box<NetworkStream + Send>> {
+        NetworkConnector::connect(self, host, port, scheme).map(Into::into)
+    }
+}
+
+/// A request which will upgrade its connection, passed to `FromResponse::from_upgrade()`.
+pub struct Upgrade<'a> {
+    /// The head of the request, after the adapter's interceptor has been applied.
+    pub head: &'a mut RequestHead,
+    /// The base URL of the adapter, if set.
+    pub base_url: Option<&'a Url>,
+    /// The connector of the adapter.
+    pub connector: &'a Connect,
+}
+
+impl<'a> Upgrade<'a> {
+    /// Get the complete URL of the request.
+    pub fn url(&self) -> Result<Url> {
+        self.head.resolve_url(self.base_url)
+    }
+}
@@ -47,6 +47,10 @@ method! {
     /// it is not meaningful to provide a body with a `DELETE` request and any endpoint
     /// that expects a body with such a request is considered non-conformant.
     pub struct Delete;
+    /// Method for `HEAD` requests.
+    ///
+    /// The response has no body.
+    pub struct Head;
 }
 
 #[doc(hidden)]
@@ -12,18 +12,33 @@ pub use self::intercept::{Interceptor, Chain};
 
 pub use self::call::Call;
 
-pub use self::request::{RequestHead, RequestBuilder, Request};
+pub use self::request::{RequestHead, RequestBuilder, Request, PathSegment};
 
 pub use self::response::{FromResponse, Raw as RawResponse};
 
+pub mod batch;
+
 pub mod body;
 
 mod call;
 
+pub mod connect;
+
 pub mod intercept;
 
 pub mod method;
 
+pub mod multipart;
+
+pub mod progress;
+
+pub mod resume;
+
 pub mod request;
 
-pub mod response;
\ No newline at end of file
+pub mod response;
+
+pub mod sse;
+
+#[cfg(feature = "tungstenite")]
+pub mod websocket;
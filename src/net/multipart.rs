@@ -0,0 +1,347 @@
+//! Multipart request bodies with arbitrary parts and per-part headers.
+//!
+//! Unlike `fields!{}`, which only supports text and file fields in `multipart/form-data`
+//! requests, `Multipart` can hold any `EagerBody` as a part, including values serialized with
+//! the request's serializer and other `Multipart` bodies, and supports the `multipart/mixed`
+//! and `multipart/related` subtypes.
+//!
+//! For example, the metadata-plus-media uploads used by many cloud storage APIs:
+//!
+//! ```rust
+//! # #[macro_use] extern crate anterofit;
+//! # #[macro_use] extern crate serde_derive;
+//! # fn main() {}
+//! use anterofit::net::body::RawBody;
+//! use anterofit::net::multipart::{Multipart, Part};
+//!
+//! #[derive(Serialize)]
+//! pub struct Metadata {
+//!     pub name: String,
+//! }
+//!
+//! service! {
+//!     pub trait UploadService {
+//!         fn upload(&self, meta: Metadata, data: Vec<u8>) {
+//!             POST("/upload");
+//!             query! { "uploadType" => "multipart" };
+//!             body!(
+//!                 Multipart::related()
+//!                     // Serialized with the request's serializer
+//!                     .part(Part::new(meta))
+//!                     .part(Part::new(RawBody::bytes(data)))
+//!             )
+//!         }
+//!     }
+//! }
+//! ```
+
+use hyper::header::{Header, HeaderFormat, Headers, ContentType};
+
+use mime_::{Attr, Mime, SubLevel, TopLevel, Value};
+
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, Cursor, Read};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use net::body::{EagerBody, Readable, ReadableResult};
+
+use serialize::Serializer;
+
+use ::Result;
+
+/// A multipart request body.
+///
+/// Parts are written in the order they were added. The boundary is generated randomly.
+#[derive(Debug)]
+pub struct Multipart<P = ()> {
+    subtype: &'static str,
+    parts: P,
+}
+
+impl Multipart {
+    /// Create an empty `multipart/form-data` body.
+    ///
+    /// Every part should be given a name with `Part::name()`.
+    pub fn form_data() -> Self {
+        Multipart::with_subtype("form-data")
+    }
+
+    /// Create an empty `multipart/mixed` body.
+    pub fn mixed() -> Self {
+        Multipart::with_subtype("mixed")
+    }
+
+    /// Create an empty `multipart/related` body.
+    ///
+    /// The `type` parameter is set to the content type of the first part, which is the root.
+    pub fn related() -> Self {
+        Multipart::with_subtype("related")
+    }
+
+    fn with_subtype(subtype: &'static str) -> Self {
+        Multipart {
+            subtype: subtype,
+            parts: (),
+        }
+    }
+}
+
+impl<P: Parts> Multipart<P> {
+    /// Add a part after those already added.
+    pub fn part<B: EagerBody + Send + 'static>(self, part: Part<B>) -> Multipart<(P, Part<B>)> {
+        Multipart {
+            subtype: self.subtype,
+            parts: (self.parts, part),
+        }
+    }
+}
+
+impl<P: Parts> EagerBody for Multipart<P> {
+    type Readable = MultipartReader;
+
+    fn into_readable<S>(self, ser: &S) -> ReadableResult<MultipartReader> where S: Serializer {
+        let boundary = boundary();
+
+        let mut reader = MultipartReader::new(self.subtype == "form-data");
+        try!(self.parts.write_parts(ser, &boundary, &mut reader));
+        reader.push_bytes(format!("--{}--\r\n", boundary).into_bytes());
+
+        let mut params = vec![(Attr::Boundary, Value::Ext(boundary))];
+
+        if self.subtype == "related" {
+            // Only the media type of the root part, without parameters (RFC 2387)
+            if let Some(Mime(ref top, ref sub, _)) = reader.root_type {
+                let root_type = Mime(top.clone(), sub.clone(), vec![]);
+                params.push((Attr::Ext("type".into()), Value::Ext(format!("\"{}\"", root_type))));
+            }
+        }
+
+        let content_type = Mime(TopLevel::Multipart, SubLevel::Ext(self.subtype.into()), params);
+        let content_length = reader.content_length;
+
+        Ok(Readable::new(reader, content_type).with_content_length(content_length))
+    }
+}
+
+/// A part of a `Multipart` body, with its own headers.
+#[derive(Debug)]
+pub struct Part<B> {
+    body: B,
+    name: Option<String>,
+    filename: Option<String>,
+    headers: Headers,
+}
+
+impl<B: EagerBody> Part<B> {
+    /// Wrap `body` as a part.
+    ///
+    /// The `Content-Type` of the part is taken from the body unless set with `header()`.
+    pub fn new(body: B) -> Self {
+        Part {
+            body: body,
+            name: None,
+            filename: None,
+            headers: Headers::new(),
+        }
+    }
+
+    /// Set the name of the part in `Content-Disposition`, required for `multipart/form-data`.
+    pub fn name<N: Into<String>>(self, name: N) -> Self {
+        Part { name: Some(name.into()), .. self }
+    }
+
+    /// Set the filename of the part in `Content-Disposition`.
+    pub fn filename<F: Into<String>>(self, filename: F) -> Self {
+        Part { filename: Some(filename.into()), .. self }
+    }
+
+    /// Set a header on this part, such as `Content-ID`, overwriting any previous value.
+    pub fn header<H: Header + HeaderFormat>(mut self, header: H) -> Self {
+        self.headers.set(header);
+        self
+    }
+
+    /// Set a header on this part by name, overwriting any previous value.
+    pub fn header_raw<K: Into<Cow<'static, str>>, V: Into<Vec<u8>>>(mut self, name: K, val: V) -> Self {
+        self.headers.set_raw(name, vec![val.into()]);
+        self
+    }
+}
+
+impl<B: EagerBody> Part<B> {
+    fn write<S: Serializer>(mut self, ser: &S, boundary: &str, reader: &mut MultipartReader) -> Result<()> {
+        let readable = try!(self.body.into_readable(ser));
+
+        if !self.headers.has::<ContentType>() {
+            if let Some(content_type) = readable.content_type {
+                self.headers.set(ContentType(content_type));
+            }
+        }
+
+        if reader.root_type.is_none() {
+            reader.root_type = Some(self.headers.get::<ContentType>()
+                .map_or_else(|| mime!(Text/Plain), |content_type| content_type.0.clone()));
+        }
+
+        let mut head = format!("--{}\r\n", boundary);
+
+        if self.name.is_some() || self.filename.is_some() {
+            head.push_str(if reader.form_data { "Content-Disposition: form-data" }
+                          else { "Content-Disposition: attachment" });
+
+            if let Some(ref name) = self.name {
+                head.push_str(&format!("; name=\"{}\"", escape_quoted(name)));
+            }
+
+            if let Some(ref filename) = self.filename {
+                head.push_str(&format!("; filename=\"{}\"", escape_quoted(filename)));
+            }
+
+            head.push_str("\r\n");
+        }
+
+        head.push_str(&self.headers.to_string());
+        head.push_str("\r\n");
+
+        reader.push_bytes(head.into_bytes());
+        reader.push(Box::new(readable.readable), readable.content_length);
+        reader.push_bytes(b"\r\n".to_vec());
+
+        Ok(())
+    }
+}
+
+/// The parts of a `Multipart` body, as a list built by `Multipart::part()`.
+pub trait Parts: Send + 'static {
+    /// Write the parts to `reader`, each preceded by `boundary`.
+    fn write_parts<S: Serializer>(self, ser: &S, boundary: &str, reader: &mut MultipartReader) -> Result<()>;
+}
+
+impl Parts for () {
+    fn write_parts<S: Serializer>(self, _: &S, _: &str, _: &mut MultipartReader) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<P: Parts, B: EagerBody + Send + 'static> Parts for (P, Part<B>) {
+    fn write_parts<S: Serializer>(self, ser: &S, boundary: &str, reader: &mut MultipartReader) -> Result<()> {
+        // Earlier parts first
+        try!(self.0.write_parts(ser, boundary, reader));
+        self.1.write(ser, boundary, reader)
+    }
+}
+
+/// The readable form of a `Multipart` body.
+pub struct MultipartReader {
+    readers: VecDeque<Box<Read + Send>>,
+    form_data: bool,
+    root_type: Option<Mime>,
+    // `None` if the length of any part is unknown
+    content_length: Option<u64>,
+}
+
+impl MultipartReader {
+    fn new(form_data: bool) -> Self {
+        MultipartReader {
+            readers: VecDeque::new(),
+            form_data: form_data,
+            root_type: None,
+            content_length: Some(0),
+        }
+    }
+
+    fn push(&mut self, read: Box<Read + Send>, len: Option<u64>) {
+        self.readers.push_back(read);
+        self.content_length = self.content_length.and_then(|total| len.map(|len| total + len));
+    }
+
+    fn push_bytes(&mut self, bytes: Vec<u8>) {
+        let len = bytes.len() as u64;
+        self.push(Box::new(Cursor::new(bytes)), Some(len));
+    }
+}
+
+impl Read for MultipartReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while let Some(read) = self.readers.front_mut().map(|read| read.read(buf)) {
+            match try!(read) {
+                0 if !buf.is_empty() => { self.readers.pop_front(); },
+                read => return Ok(read),
+            }
+        }
+
+        Ok(0)
+    }
+}
+
+impl fmt::Debug for MultipartReader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MultipartReader")
+            .field("remaining", &self.readers.len())
+            .finish()
+    }
+}
+
+/// Percent-encode characters which cannot appear in a quoted `Content-Disposition` parameter,
+/// as browsers do.
+fn escape_quoted(val: &str) -> Cow<'_, str> {
+    if !val.contains(&['"', '\r', '\n'][..]) {
+        return Cow::Borrowed(val);
+    }
+
+    Cow::Owned(val.replace('"', "%22").replace('\r', "%0D").replace('\n', "%0A"))
+}
+
+fn boundary() -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    // `RandomState` is randomly seeded for each instance
+    let state = RandomState::new();
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+
+    let mut first = state.build_hasher();
+    first.write_usize(count);
+
+    let mut second = state.build_hasher();
+    second.write_u64(first.finish());
+
+    format!("anterofit-{:016x}{:016x}", first.finish(), second.finish())
+}
+
+#[test]
+fn multipart_related() {
+    use net::body::RawBody;
+    use serialize::none::NoSerializer;
+
+    let readable = Multipart::related()
+        .part(Part::new(RawBody::text("{}")).header_raw("Content-ID", "<meta>"))
+        .part(Part::new(RawBody::bytes(vec![1, 2])).name("media").filename("a\".bin"))
+        .into_readable(&NoSerializer).unwrap();
+
+    let content_type = readable.content_type.unwrap();
+    let boundary = content_type.get_param(Attr::Boundary).unwrap().to_string();
+    assert_eq!(content_type.get_param("type").unwrap().as_str(), "\"text/plain\"");
+
+    let content_length = readable.content_length;
+
+    let mut body = Vec::new();
+    { readable.readable }.read_to_end(&mut body).unwrap();
+    assert_eq!(content_length, Some(body.len() as u64));
+
+    let body = String::from_utf8(body).unwrap();
+    let parts: Vec<&str> = body.split(&format!("--{}", boundary)).collect();
+
+    assert_eq!(parts.len(), 4);
+    assert_eq!(parts[0], "");
+    // Header order is unspecified
+    assert!(parts[1].contains("\r\nContent-Type: text/plain; charset=utf-8\r\n"));
+    assert!(parts[1].contains("\r\nContent-ID: <meta>\r\n"));
+    assert!(parts[1].ends_with("\r\n\r\n{}\r\n"));
+    assert_eq!(parts[2], "\r\nContent-Disposition: attachment; name=\"media\"; filename=\"a%22.bin\"\r\n\
+                          Content-Type: application/octet-stream\r\n\r\n\x01\x02\r\n");
+    assert_eq!(parts[3], "--\r\n");
+}
@@ -0,0 +1,248 @@
+//! Progress reporting for request and response bodies.
+//!
+//! Set callbacks with the `progress!()` macro in a service method, or on `RequestHead` from an
+//! interceptor. Upload progress is reported for any request body, and download progress as the
+//! response body is read, whether by the deserializer or from a `RawResponse`.
+//!
+//! ```rust
+//! # #[macro_use] extern crate anterofit;
+//! # fn main() {}
+//! use anterofit::net::RawResponse;
+//! use anterofit::net::progress::OnProgress;
+//! use std::path::Path;
+//!
+//! service! {
+//!     pub trait FileService {
+//!         fn upload(&self, file: &Path, progress: OnProgress) {
+//!             POST("/files");
+//!             fields! { "file" => path!(file) };
+//!             progress!(upload: progress)
+//!         }
+//!
+//!         fn download(&self, name: &str, progress: OnProgress) -> RawResponse {
+//!             GET("/files/{name}");
+//!             progress!(download: progress)
+//!         }
+//!     }
+//! }
+//! ```
+
+use hyper::header::ContentLength;
+use hyper::http::{HttpMessage, RequestHead, ResponseHead};
+
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Receiver};
+use std::time::Duration;
+
+use net::response::Response;
+
+/// The progress of a transfer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Progress {
+    /// The number of bytes transferred so far.
+    pub transferred: u64,
+    /// The total number of bytes to transfer, if known.
+    pub total: Option<u64>,
+}
+
+/// A callback invoked with the `Progress` of a transfer after every read.
+///
+/// Cheap to clone. Invoked on the thread executing the request.
+#[derive(Clone)]
+pub struct OnProgress(Arc<Fn(Progress) + Send + Sync>);
+
+impl OnProgress {
+    /// Wrap a closure as a progress callback.
+    pub fn new<F>(callback: F) -> Self where F: Fn(Progress) + Send + Sync + 'static {
+        OnProgress(Arc::new(callback))
+    }
+
+    /// Create a progress callback which sends updates over a channel.
+    ///
+    /// Updates are dropped once the receiver is dropped.
+    pub fn channel() -> (Self, Receiver<Progress>) {
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+
+        (OnProgress::new(move |progress| {
+            let _ = tx.lock().unwrap_or_else(|e| e.into_inner()).send(progress);
+        }), rx)
+    }
+
+    fn report(&self, progress: Progress) {
+        (self.0)(progress)
+    }
+}
+
+impl fmt::Debug for OnProgress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("OnProgress(Fn(Progress) + Send + Sync)")
+    }
+}
+
+/// A `Read` wrapper which reports progress to a callback.
+#[derive(Debug)]
+pub struct ProgressReader<R> {
+    inner: R,
+    progress: Progress,
+    callback: Option<OnProgress>,
+}
+
+impl<R: Read> ProgressReader<R> {
+    /// Wrap `inner`, reporting progress to `callback` (if any) out of `total` bytes.
+    pub fn new<C: Into<Option<OnProgress>>>(inner: R, total: Option<u64>, callback: C) -> Self {
+        ProgressReader {
+            inner: inner,
+            progress: Progress {
+                transferred: 0,
+                total: total,
+            },
+            callback: callback.into(),
+        }
+    }
+
+    /// Get the progress so far.
+    pub fn progress(&self) -> Progress {
+        self.progress
+    }
+
+    /// Get a reference to the wrapped reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Unwrap the inner reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for ProgressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = try!(self.inner.read(buf));
+
+        if read == 0 {
+            return Ok(0);
+        }
+
+        self.progress.transferred += read as u64;
+
+        if let Some(ref callback) = self.callback {
+            callback.report(self.progress);
+        }
+
+        Ok(read)
+    }
+}
+
+/// Wrap `response` so that reading its body reports progress to `callback`, out of its
+/// `Content-Length` if known.
+///
+/// The returned response has the same status, headers and URL.
+pub fn download(response: Response, callback: OnProgress) -> ::Result<Response> {
+    let head = ResponseHead {
+        headers: response.headers.clone(),
+        raw_status: response.status_raw().clone(),
+        version: response.version,
+    };
+
+    let url = response.url.clone();
+    let total = response.headers.get::<ContentLength>().map(|len| len.0);
+
+    let message = DownloadMessage {
+        head: Some(head),
+        body: ProgressReader::new(response, total, callback),
+    };
+
+    Response::with_message(url, Box::new(message)).map_err(Into::into)
+}
+
+/// An `HttpMessage` which yields the head of a received response and reads its body
+/// through a `ProgressReader`.
+#[derive(Debug)]
+struct DownloadMessage {
+    // Taken by `get_incoming()`
+    head: Option<ResponseHead>,
+    body: ProgressReader<Response>,
+}
+
+impl Read for DownloadMessage {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.body.read(buf)
+    }
+}
+
+impl Write for DownloadMessage {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(io::ErrorKind::InvalidInput, "the request was already sent"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl HttpMessage for DownloadMessage {
+    fn set_outgoing(&mut self, _head: RequestHead) -> ::hyper::Result<RequestHead> {
+        Err(io::Error::new(io::ErrorKind::InvalidInput, "the request was already sent").into())
+    }
+
+    fn get_incoming(&mut self) -> ::hyper::Result<ResponseHead> {
+        self.head.take()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "the response was already received").into())
+    }
+
+    fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.body.get_ref().get_ref().set_read_timeout(dur)
+    }
+
+    fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.body.get_ref().get_ref().set_write_timeout(dur)
+    }
+
+    fn close_connection(&mut self) -> ::hyper::Result<()> {
+        // The wrapped response closes the connection when dropped unless it can be reused
+        Ok(())
+    }
+
+    fn has_body(&self) -> bool {
+        self.body.get_ref().get_ref().has_body()
+    }
+}
+
+#[test]
+fn progress_reader() {
+    let (callback, updates) = OnProgress::channel();
+
+    let mut reader = ProgressReader::new(&b"Hello, world!"[..], Some(13), callback);
+    let mut buf = [0; 8];
+
+    while reader.read(&mut buf).unwrap() != 0 {}
+
+    let updates: Vec<_> = updates.try_iter().map(|p| p.transferred).collect();
+    assert_eq!(updates, [8, 13]);
+    assert_eq!(reader.progress(), Progress { transferred: 13, total: Some(13) });
+}
+
+#[test]
+fn download_progress() {
+    use net::response::{mock_response, FromResponse};
+    use serialize::FromStrDeserializer;
+
+    let (callback, updates) = OnProgress::channel();
+
+    let response = mock_response("HTTP/1.1 200 OK\r\nContent-Length: 13\r\n\r\nHello, world!");
+    let response = download(response, callback).unwrap();
+
+    assert_eq!(response.status, ::hyper::status::StatusCode::Ok);
+    assert_eq!(response.headers.get(), Some(&ContentLength(13)));
+
+    // Reported while deserializing as well as for `Raw`
+    let body: String = FromResponse::from_response(&FromStrDeserializer, response).unwrap();
+    assert_eq!(body, "Hello, world!");
+
+    let updates: Vec<_> = updates.try_iter().collect();
+    assert_eq!(updates.last(), Some(&Progress { transferred: 13, total: Some(13) }));
+}
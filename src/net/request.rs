@@ -1,16 +1,21 @@
 //! Types for constructing and issuing HTTP requests.
 
 use hyper::client::{Client, Response, RequestBuilder as NetRequestBuilder};
-use hyper::header::{Headers, Header, HeaderFormat, ContentType};
+use hyper::header::{Headers, Header, HeaderFormat, Accept, ContentLength, ContentType, TransferEncoding, qitem};
 use hyper::method::Method as HyperMethod;
 
+use mime_::Mime;
+
 use url::Url;
 use url::form_urlencoded::Serializer as FormUrlEncoded;
-use url::percent_encoding::{utf8_percent_encode, DEFAULT_ENCODE_SET};
+use url::percent_encoding::{utf8_percent_encode, DEFAULT_ENCODE_SET, PATH_SEGMENT_ENCODE_SET};
 
 use std::borrow::{Borrow, Cow};
 use std::fmt::{self, Write};
+use std::io;
 use std::mem;
+use std::ops::Deref;
+use std::sync::Arc;
 
 use adapter::{AbsAdapter, AdapterConsts};
 
@@ -20,15 +25,20 @@ use net::body::{Body, EmptyFields, EagerBody, RawBody};
 
 use net::call::Call;
 
+use net::connect::Upgrade;
+
 use net::intercept::Interceptor;
 
 use net::method::{Method, TakesBody};
 
+use net::progress::{OnProgress, ProgressReader};
+
 use net::response::FromResponse;
 
-use executor::ExecBox;
+use executor::{ExecBox, Priority};
 
-use serialize::{Serializer, Deserializer};
+use serialize::{Serialize, Deserialize, Serializer, Deserializer, ChooseSerializer, ChooseDeserializer, FromAdapter};
+use serialize::form::{self, Sequences};
 
 use ::Result;
 
@@ -38,7 +48,15 @@ pub struct RequestHead {
     url: Cow<'static, str>,
     query: String,
     method: HyperMethod,
-    headers: Headers
+    headers: Headers,
+    // Boxed as `RequestHead` is carried by `Error`
+    progress: Option<Box<ProgressCallbacks>>,
+}
+
+#[derive(Debug, Default)]
+struct ProgressCallbacks {
+    upload: Option<OnProgress>,
+    download: Option<OnProgress>,
 }
 
 impl RequestHead {
@@ -48,6 +66,7 @@ impl RequestHead {
             query: String::new(),
             method: method,
             headers: Headers::new(),
+            progress: None,
         }
     }
 
@@ -60,6 +79,25 @@ impl RequestHead {
         self
     }
 
+    /// Set an HTTP header by name, overwriting any previous value.
+    ///
+    /// `name` can be any of: `String`, `&'static str` or `Cow<'static, str>`.
+    /// `value` will be formatted with `Display` and sent as-is.
+    ///
+    /// ##Note
+    /// Some headers, such as `Content-Type`, may be overwritten by Anterofit.
+    ///
+    /// ##Panics
+    /// If an error is returned from `<V as Display>::fmt()`.
+    pub fn raw_header<K, V>(&mut self, name: K, value: V) -> &mut Self
+    where K: Into<Cow<'static, str>>, V: fmt::Display {
+        let mut buf = String::new();
+        write!(buf, "{}", value).expect("Error returned from Display::fmt()");
+
+        self.headers.set_raw(name, vec![buf.into_bytes()]);
+        self
+    }
+
     /// Copy all the HTTP headers from `headers` into this request.
     ///
     /// Duplicate headers will be overwritten.
@@ -71,6 +109,24 @@ impl RequestHead {
         self
     }
 
+    /// Report the progress of sending the request body to `callback`, replacing any previous
+    /// callback.
+    ///
+    /// The total is known if the `Content-Length` header is set.
+    pub fn upload_progress(&mut self, callback: OnProgress) -> &mut Self {
+        self.progress.get_or_insert_with(Default::default).upload = Some(callback);
+        self
+    }
+
+    /// Report the progress of reading the response body to `callback`, replacing any previous
+    /// callback.
+    ///
+    /// The total is known if the response has a `Content-Length` header.
+    pub fn download_progress(&mut self, callback: OnProgress) -> &mut Self {
+        self.progress.get_or_insert_with(Default::default).download = Some(callback);
+        self
+    }
+
     /// Append `append` to the URL of this request.
     ///
     /// If this causes the request's URL to be malformed, an error will immediately
@@ -152,6 +208,19 @@ impl RequestHead {
         self
     }
 
+    /// Serialize `query` as a series of query pairs and add them to the URL.
+    ///
+    /// `query` must serialize as a struct or map; `None` values are skipped and nested
+    /// values are flattened using brackets, e.g. `filter[owner]=me`. `sequences` sets how
+    /// sequences are written.
+    ///
+    /// Use `query!(SERIALIZE: ..)` in service methods.
+    pub fn serialize_query<Q>(&mut self, query: &Q, sequences: Sequences) -> Result<&mut Self>
+    where Q: Serialize + ?Sized {
+        let pairs = try!(form::to_pairs(query, sequences));
+        Ok(self.query(pairs))
+    }
+
     /// Initialize a `hyper::client::RequestBuilder` with the parameters in this header.
     ///
     /// If provided, `base_url` will be prepended to the URL associated with this request,
@@ -160,6 +229,15 @@ impl RequestHead {
     /// Finally, `client` will be used to create the `RequestBuilder` and the contained headers
     /// will be added.
     pub fn init_request<'c>(&self, base_url: Option<&Url>, client: &'c Client) -> Result<NetRequestBuilder<'c>> {
+        let url = try!(self.resolve_url(base_url));
+
+        // This `.clone()` should be zero-cost, we don't expose Method::Extension at all.
+        Ok(client.request(self.method.clone(), url).headers(self.headers.clone()))
+    }
+
+    /// Get the complete URL of this request, joined to `base_url` if provided and with the
+    /// constructed query set.
+    pub fn resolve_url(&self, base_url: Option<&Url>) -> Result<Url> {
         let mut url = if let Some(base_url) = base_url {
             try!(base_url.join(&self.url))
         } else {
@@ -168,8 +246,7 @@ impl RequestHead {
 
         url.set_query(Some(&self.query));
 
-        // This `.clone()` should be zero-cost, we don't expose Method::Extension at all.
-        Ok(client.request(self.method.clone(), url).headers(self.headers.clone()))
+        Ok(url)
     }
 
     /// Get the current URL of this request.
@@ -199,14 +276,72 @@ impl fmt::Display for RequestHead {
     }
 }
 
+/// Wraps a value to be substituted into a URL path so that it is formatted as a single segment.
+///
+/// Characters which are not allowed in a path segment, including `/`, `?` and `#`,
+/// will be percent-encoded.
+///
+/// Method parameters are wrapped in this automatically when they are substituted into the URL
+/// of a service method. It dereferences to the wrapped value, so fields and methods can still be
+/// accessed; the results of those will not be encoded.
+///
+/// `Debug` is encoded like `Display`, while the integer formatting traits (`{:x}`, `{:o}` etc.)
+/// are passed through, as their output never needs encoding.
+pub struct PathSegment<T>(pub T);
+
+impl<T> Deref for PathSegment<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for PathSegment<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_segment(f, &self.0.to_string())
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for PathSegment<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_segment(f, &format!("{:?}", self.0))
+    }
+}
+
+fn write_segment(f: &mut fmt::Formatter, segment: &str) -> fmt::Result {
+    for part in utf8_percent_encode(segment, PATH_SEGMENT_ENCODE_SET) {
+        try!(f.write_str(part));
+    }
+
+    Ok(())
+}
+
+macro_rules! forward_fmt {
+    ($($fmt:ident),+) => ($(
+        impl<T: fmt::$fmt> fmt::$fmt for PathSegment<T> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                fmt::$fmt::fmt(&self.0, f)
+            }
+        }
+    )+)
+}
+
+forward_fmt!(LowerHex, UpperHex, Octal, Binary);
+
 /// A container for a request header and body.
 ///
 /// Used in the body of service methods to construct a request.
+///
+/// `S` and `D` select the serializer for the request body and the deserializer for the response;
+/// by default (`FromAdapter`), these are the adapter's.
 #[derive(Debug)]
-pub struct RequestBuilder<'a, A: 'a + ?Sized, M, B> {
+pub struct RequestBuilder<'a, A: 'a + ?Sized, M, B, S = FromAdapter, D = FromAdapter> {
     head: RequestHead,
     method: M,
     body: B,
+    serializer: S,
+    deserializer: D,
     adapter: &'a A,
 }
 
@@ -220,11 +355,13 @@ impl<'a, A: 'a + ?Sized, M> RequestBuilder<'a, A, M, EmptyFields> where M: Metho
             head: RequestHead::new(method.to_hyper(), url),
             method: method,
             body: EmptyFields,
+            serializer: FromAdapter,
+            deserializer: FromAdapter,
         }
     }
 }
 
-impl<'a, A: 'a + ?Sized, M, B> RequestBuilder<'a, A, M, B> {
+impl<'a, A: 'a + ?Sized, M, B, S, D> RequestBuilder<'a, A, M, B, S, D> {
     /// Get a reference to the header of the request to inspect it.
     pub fn head(&self) -> &RequestHead {
         &self.head
@@ -242,13 +379,52 @@ impl<'a, A: 'a + ?Sized, M, B> RequestBuilder<'a, A, M, B> {
     /// arbitrarily.
     ///
     /// `try!()` will work in this closure.
-    pub fn apply<F, B_>(self, functor: F) -> Result<RequestBuilder<'a, A, M, B_>>
-    where F: FnOnce(Self) -> Result<RequestBuilder<'a, A, M, B_>> {
+    pub fn apply<F, B_, S_, D_>(self, functor: F) -> Result<RequestBuilder<'a, A, M, B_, S_, D_>>
+    where F: FnOnce(Self) -> Result<RequestBuilder<'a, A, M, B_, S_, D_>> {
         functor(self)
     }
 
+    /// Serialize `query` as a series of query pairs and add them to the URL.
+    ///
+    /// See `RequestHead::serialize_query()` for details.
+    pub fn serialize_query<Q>(mut self, query: &Q, sequences: Sequences) -> Result<Self>
+    where Q: Serialize + ?Sized {
+        try!(self.head.serialize_query(query, sequences));
+        Ok(self)
+    }
+
+    /// Serialize the body of this request with `serializer` instead of the adapter's serializer.
+    ///
+    /// This also determines the `Content-Type` header sent with the body. Call this before
+    /// `body_eager()`, which serializes the body immediately.
+    pub fn serializer<S_>(self, serializer: S_) -> RequestBuilder<'a, A, M, B, S_, D>
+    where S_: Serializer {
+        RequestBuilder {
+            adapter: self.adapter,
+            head: self.head,
+            method: self.method,
+            body: self.body,
+            serializer: serializer,
+            deserializer: self.deserializer,
+        }
+    }
+
+    /// Deserialize the response to this request with `deserializer` instead of the adapter's
+    /// deserializer.
+    pub fn deserializer<D_>(self, deserializer: D_) -> RequestBuilder<'a, A, M, B, S, D_>
+    where D_: Deserializer {
+        RequestBuilder {
+            adapter: self.adapter,
+            head: self.head,
+            method: self.method,
+            body: self.body,
+            serializer: self.serializer,
+            deserializer: deserializer,
+        }
+    }
+
     #[doc(hidden)]
-    pub fn swap_method<M_>(self, method: M_) -> (RequestBuilder<'a, A, M_, B>, M) {
+    pub fn swap_method<M_>(self, method: M_) -> (RequestBuilder<'a, A, M_, B, S, D>, M) {
         let old_method = self.method;
 
         (
@@ -256,6 +432,8 @@ impl<'a, A: 'a + ?Sized, M, B> RequestBuilder<'a, A, M, B> {
                 head: self.head,
                 method: method,
                 body: self.body,
+                serializer: self.serializer,
+                deserializer: self.deserializer,
                 adapter: self.adapter,
             },
             old_method
@@ -263,17 +441,22 @@ impl<'a, A: 'a + ?Sized, M, B> RequestBuilder<'a, A, M, B> {
     }
 }
 
-impl<'a, A: 'a + ?Sized, M, B> RequestBuilder<'a, A, M, B> where A: AbsAdapter, M: TakesBody {
+/// The result of `RequestBuilder::body_eager()`.
+type EagerBuilder<'a, A, M, B, S, D> = RequestBuilder<'a, A, M, RawBody<<B as EagerBody>::Readable>, S, D>;
+
+impl<'a, A: 'a + ?Sized, M, B, S, D> RequestBuilder<'a, A, M, B, S, D> where A: AbsAdapter, M: TakesBody {
     /// Set a body to be sent with the request.
     ///
     /// Generally, `GET` and `DELETE` are not to have bodies
     // If you need to have a body on a GET or DELETE request
-    pub fn body<B_>(self, body: B_) -> RequestBuilder<'a, A, M, B_> {
+    pub fn body<B_>(self, body: B_) -> RequestBuilder<'a, A, M, B_, S, D> {
         RequestBuilder {
             adapter: self.adapter,
             head: self.head,
             method: self.method,
             body: body,
+            serializer: self.serializer,
+            deserializer: self.deserializer,
         }
     }
 
@@ -285,22 +468,27 @@ impl<'a, A: 'a + ?Sized, M, B> RequestBuilder<'a, A, M, B> where A: AbsAdapter,
     /// ##Panics
     /// If this is a GET request (cannot have a body).
     pub fn body_eager<B_>(self, body: B_)
-        -> Result<RequestBuilder<'a, A, M, RawBody<<B_ as EagerBody>::Readable>>>
-        where B_: EagerBody {
+        -> Result<EagerBuilder<'a, A, M, B_, S, D>>
+        where B_: EagerBody, S: ChooseSerializer<A::Ser> {
+
+        let body = {
+            let serializer = self.serializer.choose(&self.adapter.ref_consts().serializer);
+            try!(body.into_readable(serializer)).into()
+        };
 
-        let body = try!(body.into_readable(&self.adapter.ref_consts().serializer)).into();
         Ok(self.body(body))
     }
 }
 
-impl<'a, A: 'a + ?Sized, M, B> RequestBuilder<'a, A, M, B> where A: AbsAdapter {
+impl<'a, A: 'a + ?Sized, M, B, S, D> RequestBuilder<'a, A, M, B, S, D> where A: AbsAdapter {
     /// Prepare a `Request` to be executed with the parameters supplied in this builder.
     ///
     /// This request will need to be executed (using `exec()` or `exec_here()`) before anything
     /// else is done. As much work as possible will be relegated to the adapter's executor.
-    pub fn build<T>(self) -> Request<'a, T> where B: Body, T: FromResponse {
+    pub fn build<T>(self) -> Request<'a, T>
+    where B: Body, T: FromResponse, S: ChooseSerializer<A::Ser>, D: ChooseDeserializer<A::De> {
         let RequestBuilder {
-            adapter, head, method: _method, body
+            adapter, head, method: _method, body, serializer, deserializer
         } = self;
 
         let consts = adapter.consts();
@@ -310,11 +498,30 @@ impl<'a, A: 'a + ?Sized, M, B> RequestBuilder<'a, A, M, B> where A: AbsAdapter {
 
         let exec = ExecRequest {
             sender: &adapter.ref_consts().sender,
+            priority: Priority::default(),
             exec: Box::new(move || {
                 let interceptor = interceptor.as_ref().map(|i| &**i);
 
-                let res = exec_request(&consts, interceptor, guard.head_mut(), body)
-                    .and_then(|response| T::from_response(&consts.deserializer, response));
+                if T::UPGRADE {
+                    let res = exec_upgrade(consts, serializer, deserializer, interceptor, guard.head_mut());
+                    guard.complete(res);
+                    return;
+                }
+
+                let serializer = serializer.choose(&consts.serializer);
+                let deserializer = deserializer.choose(&consts.deserializer);
+
+                set_accept(guard.head_mut(), deserializer);
+
+                let res = exec_request(&consts, serializer, interceptor, guard.head_mut(), body)
+                    .and_then(|response| {
+                        let response = match guard.head_mut().progress.as_ref().and_then(|p| p.download.clone()) {
+                            Some(progress) => try!(super::progress::download(response, progress)),
+                            None => response,
+                        };
+
+                        T::from_response(deserializer, response)
+                    });
 
                 guard.complete(res);
             }),
@@ -329,12 +536,13 @@ impl<'a, A: 'a + ?Sized, M, B> RequestBuilder<'a, A, M, B> where A: AbsAdapter {
 
 struct ExecRequest<'a> {
     sender: &'a Sender,
+    priority: Priority,
     exec: Box<ExecBox>,
 }
 
 impl<'a> ExecRequest<'a> {
-    fn exec(self) {
-        self.sender.send(self.exec);
+    fn exec(self) -> Result<()> {
+        self.sender.send(self.exec, self.priority)
     }
 
     fn exec_here(self) {
@@ -376,13 +584,32 @@ impl<'a, T> Request<'a, T> {
     pub fn is_immediate(&self) -> bool {
         self.call.is_available()
     }
+
+    /// Set the priority of this request in the adapter's executor queue, which determines
+    /// the order in which waiting requests are executed by `exec()`.
+    ///
+    /// Defaults to `Priority::Normal`. Has no effect on `exec_here()` or immediate results.
+    pub fn priority(mut self, priority: Priority) -> Self {
+        if let Some(ref mut exec) = self.exec {
+            exec.priority = priority;
+        }
+
+        self
+    }
 }
 
 impl<'a, T> Request<'a, T> where T: Send + 'static {
     /// Execute this request on the adapter's executor, returning a type which can
     /// be polled for the result.
+    ///
+    /// If the executor queue is bounded and full, the behavior depends on its
+    /// `OverflowPolicy`; with `OverflowPolicy::Reject`, the returned `Call` will immediately
+    /// yield `Error::QueueFull`.
     pub fn exec(self) -> Call<T> {
-        self.exec.map(ExecRequest::exec);
+        if let Some(Err(e)) = self.exec.map(ExecRequest::exec) {
+            return super::call::immediate(Err(e));
+        }
+
         self.call
     }
 
@@ -436,12 +663,13 @@ impl<'a, T> Request<'a, T> where T: Send + 'static {
             return Request::immediate(res);
         }
 
-        let ExecRequest { exec, sender } = exec.expect("`self.exec` was `None` when it shouldn't be");
+        let ExecRequest { exec, sender, priority } = exec.expect("`self.exec` was `None` when it shouldn't be");
 
         let (mut guard, new_call) = super::call::oneshot(None);
 
         let new_exec = ExecRequest {
             sender: sender,
+            priority: priority,
             exec: Box::new(move || {
                 exec.exec();
 
@@ -458,20 +686,111 @@ impl<'a, T> Request<'a, T> where T: Send + 'static {
     }
 }
 
-fn exec_request<S, D, B>(consts: &AdapterConsts<S, D>, interceptor: Option<&Interceptor>, head: &mut RequestHead, body: B) -> Result<Response>
-where S: Serializer, D: Deserializer, B: Body {
+fn exec_request<S, D, S_, B>(consts: &AdapterConsts<S, D>, serializer: &S_, interceptor: Option<&Interceptor>,
+                            head: &mut RequestHead, body: B) -> Result<Response>
+where S: Serializer, D: Deserializer, S_: Serializer, B: Body {
     if let Some(interceptor) = interceptor {
         interceptor.intercept(head);
     }
 
-    let mut readable = try!(body.into_readable(&consts.serializer));
+    let readable = try!(body.into_readable(serializer));
 
     if let Some(content_type) = readable.content_type {
         head.header(ContentType(content_type));
     }
 
-    head.init_request(consts.base_url.as_ref(), &consts.client)?
-        .body(&mut readable.readable).send().map_err(Into::into)
+    set_content_length(head, readable.content_length);
+
+    let total = head.headers.get::<ContentLength>().map(|len| len.0);
+    let callback = head.progress.as_ref().and_then(|p| p.upload.clone());
+    let mut readable = ProgressReader::new(readable.readable, total, callback);
+
+    let request = try!(head.init_request(consts.base_url.as_ref(), &consts.client));
+
+    request.body(&mut readable).send().map_err(Into::into)
+}
+
+/// Upgrade the connection instead of sending the request with `hyper::Client`; the body is not sent.
+fn exec_upgrade<T, S, D, S_, D_>(consts: Arc<AdapterConsts<S, D>>, serializer: S_, deserializer: D_,
+                                 interceptor: Option<&Interceptor>, head: &mut RequestHead) -> Result<T>
+where T: FromResponse, S: Serializer, D: Deserializer, S_: ChooseSerializer<S>, D_: ChooseDeserializer<D> {
+    if let Some(interceptor) = interceptor {
+        interceptor.intercept(head);
+    }
+
+    let upgrade = Upgrade {
+        head: head,
+        base_url: consts.base_url.as_ref(),
+        connector: &*consts.connector,
+    };
+
+    let codec = Chosen {
+        consts: consts.clone(),
+        serializer: serializer,
+        deserializer: deserializer,
+    };
+
+    T::from_upgrade(upgrade, codec)
+}
+
+/// The serializer and deserializer chosen for a request, kept for the life of an upgraded connection.
+struct Chosen<S, D, S_, D_> {
+    consts: Arc<AdapterConsts<S, D>>,
+    serializer: S_,
+    deserializer: D_,
+}
+
+impl<S, D, S_, D_> Serializer for Chosen<S, D, S_, D_>
+where S: Serializer, D: Deserializer, S_: ChooseSerializer<S>, D_: ChooseDeserializer<D> {
+    fn serialize<T: Serialize, W: io::Write>(&self, val: &T, write: &mut W) -> Result<()> {
+        self.serializer.choose(&self.consts.serializer).serialize(val, write)
+    }
+
+    fn content_type(&self) -> Option<Mime> {
+        self.serializer.choose(&self.consts.serializer).content_type()
+    }
+}
+
+impl<S, D, S_, D_> Deserializer for Chosen<S, D, S_, D_>
+where S: Serializer, D: Deserializer, S_: ChooseSerializer<S>, D_: ChooseDeserializer<D> {
+    fn deserialize<T: Deserialize, R: io::Read>(&self, read: &mut R) -> Result<T> {
+        self.deserializer.choose(&self.consts.deserializer).deserialize(read)
+    }
+
+    fn accept(&self) -> Vec<Mime> {
+        self.deserializer.choose(&self.consts.deserializer).accept()
+    }
+}
+
+/// Set `Content-Length` if the length of the body is known, unless it or `Transfer-Encoding`
+/// was already set.
+///
+/// Hyper sends bodies without a `Content-Length` chunked, adding `Transfer-Encoding` itself.
+fn set_content_length(head: &mut RequestHead, content_length: Option<u64>) {
+    // Hyper doesn't send bodies with these methods
+    if head.method == HyperMethod::Get || head.method == HyperMethod::Head {
+        return;
+    }
+
+    if head.headers.has::<ContentLength>() || head.headers.has::<TransferEncoding>() {
+        return;
+    }
+
+    if let Some(len) = content_length {
+        head.header(ContentLength(len));
+    }
+}
+
+fn set_accept<D: Deserializer>(head: &mut RequestHead, deserializer: &D) {
+    if head.headers.has::<Accept>() {
+        return;
+    }
+
+    let accept = deserializer.accept();
+
+    if !accept.is_empty() {
+        head.header(Accept(accept.into_iter().map(qitem).collect()));
+    }
 }
 
 // FIXME: stable in 1.16
@@ -485,3 +804,96 @@ fn prepend_str(prepend: &str, to: &mut String) {
 fn prepend_str(prepend: &str, to: &mut String) {
     *to = prepend.to_string() + to;
 }
+
+#[test]
+fn path_segment_encoding() {
+    assert_eq!(PathSegment("a/b?c#d e").to_string(), "a%2Fb%3Fc%23d%20e");
+    assert_eq!(PathSegment(42).to_string(), "42");
+    assert_eq!(format!("{:?}", PathSegment("a/b")), "%22a%2Fb%22");
+    assert_eq!(format!("{:04x}", PathSegment(255)), "00ff");
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn serializer_override() {
+    use net::method::Post;
+    use serialize::json;
+
+    let adapter = ::Adapter::builder().build();
+
+    // The default adapter has no serializer.
+    assert!(RequestBuilder::new(&adapter, Post, "/".into()).body_eager(vec![1, 2]).is_err());
+
+    let builder = RequestBuilder::new(&adapter, Post, "/".into())
+        .serializer(json::Serializer)
+        .body_eager(vec![1, 2]);
+
+    assert!(builder.is_ok());
+}
+
+#[test]
+fn body_length_headers() {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::Duration;
+
+    use net::body::RawBody;
+    use net::method::{ForceBody, Get, Post};
+    use net::response::Raw;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    // Sends back the headers of each request, lowercased
+    let server = thread::spawn(move || {
+        let mut requests = Vec::new();
+
+        for _ in 0 .. 3 {
+            let mut stream = BufReader::new(listener.accept().unwrap().0);
+            let mut headers = Vec::new();
+            let mut line = String::new();
+
+            while stream.read_line(&mut line).unwrap() > 2 {
+                headers.push(line.trim_end().to_lowercase());
+                line.clear();
+            }
+
+            // Read the body, if any, so closing the connection doesn't reset it
+            stream.get_ref().set_read_timeout(Some(Duration::from_millis(50))).unwrap();
+            let _ = stream.read_to_end(&mut Vec::new());
+
+            stream.get_mut().write_all(b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n").unwrap();
+            requests.push(headers);
+        }
+
+        requests
+    });
+
+    let adapter = ::Adapter::builder()
+        .base_url(format!("http://127.0.0.1:{}/", port).parse().unwrap())
+        .build();
+
+    let unknown = || RawBody::new(&b"Hello"[..], None);
+
+    RequestBuilder::new(&adapter, Post, "unknown".into()).body(unknown()).build::<Raw>().exec_here().unwrap();
+    RequestBuilder::new(&adapter, Post, "known".into()).body(unknown().with_content_length(5))
+        .build::<Raw>().exec_here().unwrap();
+
+    let (builder, _) = RequestBuilder::new(&adapter, Get, "get".into()).swap_method(ForceBody);
+    let (builder, _) = builder.body(unknown().with_content_length(5)).swap_method(Get);
+    builder.build::<Raw>().exec_here().unwrap();
+
+    let requests = server.join().unwrap();
+    let count = |headers: &[String], prefix: &str| headers.iter().filter(|h| h.starts_with(prefix)).count();
+
+    assert_eq!(requests[0].iter().filter(|h| h.starts_with("transfer-encoding")).collect::<Vec<_>>(),
+               ["transfer-encoding: chunked"]);
+    assert_eq!(count(&requests[0], "content-length"), 0);
+
+    assert!(requests[1].contains(&"content-length: 5".to_owned()), "{:?}", requests[1]);
+    assert_eq!(count(&requests[1], "transfer-encoding"), 0);
+
+    assert_eq!(count(&requests[2], "content-length") + count(&requests[2], "transfer-encoding"), 0,
+               "{:?}", requests[2]);
+}
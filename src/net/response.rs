@@ -4,7 +4,9 @@ pub use hyper::client::Response;
 
 use std::io::{self, Read};
 
-use serialize::{Deserialize, Deserializer};
+use net::connect::Upgrade;
+
+use serialize::{Deserialize, Deserializer, Serializer};
 
 use ::Result;
 
@@ -18,12 +20,25 @@ pub trait FromResponse: Send + Sized + 'static {
     /// Deserialize or otherwise convert an instance of `Self` from `response`.
     fn from_response<D>(des: &D, response: Response) -> Result<Self>
         where D: Deserializer;
+
+    /// If `true`, `from_upgrade()` is called to open a connection using another protocol instead
+    /// of sending the request and calling `from_response()`.
+    const UPGRADE: bool = false;
+
+    /// Upgrade the connection for the request, encoding and decoding messages with `codec`,
+    /// which uses the serializer and deserializer chosen for the request.
+    ///
+    /// Only called if `UPGRADE` is `true`. The default implementation returns an error.
+    fn from_upgrade<C>(_upgrade: Upgrade, _codec: C) -> Result<Self>
+        where C: Serializer + Deserializer {
+        Err(io::Error::new(io::ErrorKind::InvalidInput, "this type does not upgrade connections").into())
+    }
 }
 
 impl<T> FromResponse for T where T: Deserialize + Send + 'static {
     fn from_response<D>(des: &D, mut response: Response) -> Result<Self>
         where D: Deserializer {
-        des.deserialize(&mut response)
+        des.deserialize_response(&mut response)
     }
 }
 
@@ -74,7 +89,7 @@ pub struct WithRaw<T> {
 impl<T> FromResponse for WithRaw<T> where T: Deserialize + Send + 'static {
     fn from_response<D>(des: &D, mut response: Response) -> Result<Self>
         where D: Deserializer {
-        let val = try!(des.deserialize(&mut response));
+        let val = try!(des.deserialize_response(&mut response));
         Ok(WithRaw {
             raw: response,
             value: val
@@ -100,7 +115,7 @@ pub struct TryWithRaw<T> {
 impl<T> FromResponse for TryWithRaw<T> where T: Deserialize + Send + 'static {
     fn from_response<D>(des: &D, mut response: Response) -> Result<Self>
         where D: Deserializer {
-        let res = des.deserialize(&mut response);
+        let res = des.deserialize_response(&mut response);
         Ok(TryWithRaw {
             raw: response,
             result: res,
@@ -112,4 +127,46 @@ impl<T> Into<Result<T>> for TryWithRaw<T> {
     fn into(self) -> Result<T> {
         self.result
     }
-}
\ No newline at end of file
+}
+/// Parse `raw` as an HTTP response from a server.
+#[cfg(test)]
+pub fn mock_response(raw: &str) -> Response {
+    use hyper::net::NetworkStream;
+    use std::net::SocketAddr;
+    use std::time::Duration;
+
+    struct MockStream(io::Cursor<Vec<u8>>);
+
+    impl Read for MockStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    impl io::Write for MockStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl NetworkStream for MockStream {
+        fn peer_addr(&mut self) -> io::Result<SocketAddr> {
+            Ok("127.0.0.1:80".parse().unwrap())
+        }
+
+        fn set_read_timeout(&self, _dur: Option<Duration>) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn set_write_timeout(&self, _dur: Option<Duration>) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let stream = MockStream(io::Cursor::new(raw.as_bytes().to_owned()));
+    Response::new("http://localhost/".parse().unwrap(), Box::new(stream)).unwrap()
+}
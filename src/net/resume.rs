@@ -0,0 +1,467 @@
+//! Resumable transfers: ranged downloads into a partial file, and chunked uploads.
+//!
+//! Transfers are retried after connection and I/O errors, resuming from where they stopped.
+//! The delay between attempts doubles after each consecutive failure, and is reset along with
+//! the failure count whenever an attempt makes progress.
+//!
+//! ```rust,no_run
+//! # #[macro_use] extern crate anterofit;
+//! use anterofit::Adapter;
+//! use anterofit::net::header::Range;
+//! use anterofit::net::response::Raw;
+//! use anterofit::net::resume::{ResumableDownload, ResumableUpload, UploadProtocol};
+//! use std::fs::File;
+//!
+//! service! {
+//!     pub trait FileService {
+//!         fn download(&self, range: Range) -> Raw {
+//!             GET("/files/backup.tar");
+//!             headers! { "Range" => range }
+//!         }
+//!     }
+//! }
+//!
+//! # fn main() {
+//! let adapter = Adapter::builder()
+//!     .base_url("https://example.com".parse().unwrap())
+//!     .build();
+//!
+//! // Appends to `backup.tar` if it exists from a previous, interrupted download.
+//! ResumableDownload::new("backup.tar")
+//!     .run(|range| adapter.download(range).exec_here())
+//!     .unwrap();
+//!
+//! // The session URL is returned by the API when creating the upload.
+//! let file = File::open("backup.tar").unwrap();
+//! let total = file.metadata().unwrap().len();
+//!
+//! ResumableUpload::new("https://example.com/uploads/1234", UploadProtocol::Tus)
+//!     .run(&adapter, file, total)
+//!     .unwrap();
+//! # }
+//! ```
+
+use hyper::header::{ByteRangeSpec, ContentLength, ContentRange, ContentRangeSpec, Headers, Range};
+use hyper::status::StatusCode;
+
+use std::borrow::Cow;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::str;
+use std::thread;
+use std::time::Duration;
+
+use adapter::AbsAdapter;
+
+use mime;
+
+use net::body::RawBody;
+use net::method::{Head, Patch, Put};
+use net::request::RequestBuilder;
+use net::response::Raw;
+
+use ::{Error, Result};
+
+const DEFAULT_ATTEMPTS: u32 = 5;
+
+const DEFAULT_INITIAL_DELAY_MS: u64 = 1000;
+
+const DEFAULT_MAX_DELAY_MS: u64 = 30_000;
+
+/// A download which appends to a partial file, requesting only the bytes it does not have yet.
+///
+/// The partial file is kept after errors, so the download can also be resumed in a later run.
+#[derive(Clone, Debug)]
+pub struct ResumableDownload {
+    path: PathBuf,
+    retry: Retry,
+}
+
+impl ResumableDownload {
+    /// Download to the file at `path`, resuming from its current length if it exists.
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        ResumableDownload {
+            path: path.into(),
+            retry: Retry::default(),
+        }
+    }
+
+    /// Set the number of consecutive failed attempts before giving up. Defaults to 5.
+    ///
+    /// An attempt which appended to the file before failing resets the count.
+    pub fn attempts(mut self, attempts: u32) -> Self {
+        self.retry.attempts = attempts;
+        self
+    }
+
+    /// Set the delay before the first retry after a failure, and the maximum delay it doubles up
+    /// to after each consecutive failure. Defaults to 1 and 30 seconds.
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.retry.initial_delay = initial;
+        self.retry.max_delay = max;
+        self
+    }
+
+    /// Get the offset the download will resume from: the length of the partial file, or 0.
+    pub fn offset(&self) -> io::Result<u64> {
+        match fs::metadata(&self.path) {
+            Ok(meta) => Ok(meta.len()),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Run the download, calling `request` with the `Range` header to send for each attempt.
+    ///
+    /// Returns the length of the completed file.
+    ///
+    /// If the server ignores the range and sends the whole body, the file is overwritten.
+    pub fn run<F>(&self, mut request: F) -> Result<u64> where F: FnMut(Range) -> Result<Raw> {
+        let progress = || self.offset().unwrap_or(0);
+
+        self.retry.run(progress, || {
+            let offset = try!(self.offset());
+            let raw = try!(request(Range::Bytes(vec![ByteRangeSpec::AllFrom(offset)])));
+            self.write(raw, offset)
+        })
+    }
+
+    fn write(&self, mut raw: Raw, offset: u64) -> Result<u64> {
+        let mut file = match raw.0.status {
+            StatusCode::PartialContent => {
+                match raw.0.headers.get::<ContentRange>() {
+                    Some(&ContentRange(ContentRangeSpec::Bytes { range: Some((start, _)), .. }))
+                        if start == offset => (),
+                    _ => return Err(resume_error(ResumeError::Offset)),
+                }
+
+                try!(OpenOptions::new().append(true).create(true).open(&self.path))
+            },
+            StatusCode::Ok => try!(File::create(&self.path)),
+            // Already complete
+            StatusCode::RangeNotSatisfiable if offset > 0 => match raw.0.headers.get::<ContentRange>() {
+                Some(&ContentRange(ContentRangeSpec::Bytes { instance_length: Some(len), .. }))
+                    if len == offset => return Ok(offset),
+                _ => return Err(resume_error(ResumeError::Offset)),
+            },
+            status => return Err(resume_error(ResumeError::Status(status))),
+        };
+
+        try!(io::copy(&mut raw, &mut file));
+
+        Ok(try!(file.metadata()).len())
+    }
+}
+
+/// The protocol used by a `ResumableUpload`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UploadProtocol {
+    /// Chunks are sent with `PUT` and `Content-Range: bytes {first}-{last}/{total}`; the server
+    /// responds `308` with the `Range` it has received until the upload completes.
+    ///
+    /// Used by Google Cloud Storage and Google Drive, among others.
+    ContentRange,
+    /// The [tus](https://tus.io) 1.0 protocol: chunks are sent with `PATCH` and `Upload-Offset`,
+    /// and the offset is queried with `HEAD`.
+    Tus,
+}
+
+/// An upload to an existing upload session, sent in chunks so that it can be resumed from the
+/// last chunk the server received.
+#[derive(Clone, Debug)]
+pub struct ResumableUpload {
+    url: Cow<'static, str>,
+    protocol: UploadProtocol,
+    chunk_size: u64,
+    retry: Retry,
+}
+
+impl ResumableUpload {
+    /// Upload to the session at `url`, which is joined to the adapter's base URL if it has one.
+    pub fn new<U: Into<Cow<'static, str>>>(url: U, protocol: UploadProtocol) -> Self {
+        ResumableUpload {
+            url: url.into(),
+            protocol: protocol,
+            chunk_size: 8 * 1024 * 1024,
+            retry: Retry::default(),
+        }
+    }
+
+    /// Set the maximum size of each chunk in bytes. Defaults to 8 MiB.
+    ///
+    /// Some servers require a multiple of a certain size, such as 256 KiB for Google Cloud Storage.
+    pub fn chunk_size(self, chunk_size: u64) -> Self {
+        ResumableUpload { chunk_size: chunk_size, .. self }
+    }
+
+    /// Set the number of consecutive failed attempts before giving up. Defaults to 5.
+    ///
+    /// The count is reset after every chunk the server receives.
+    pub fn attempts(mut self, attempts: u32) -> Self {
+        self.retry.attempts = attempts;
+        self
+    }
+
+    /// Set the delay before the first retry after a failure, and the maximum delay it doubles up
+    /// to after each consecutive failure. Defaults to 1 and 30 seconds.
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.retry.initial_delay = initial;
+        self.retry.max_delay = max;
+        self
+    }
+
+    /// Ask the server how many bytes of the upload it has received.
+    pub fn offset<A>(&self, adapter: &A, total: u64) -> Result<u64> where A: AbsAdapter {
+        self.query(adapter, total).map(|step| match step {
+            Step::Continue(offset) => offset,
+            Step::Done(_) => total,
+        })
+    }
+
+    /// Upload `total` bytes from `source`, starting from the offset the server has received.
+    ///
+    /// Returns the response to the final chunk.
+    pub fn run<A, R>(&self, adapter: &A, mut source: R, total: u64) -> Result<Raw>
+    where A: AbsAdapter, R: Read + Seek {
+        let mut offset = None;
+
+        loop {
+            // Each chunk is retried separately, so failures are only counted until one is received
+            let step = self.retry.run(|| 0, || {
+                let start = match offset.take() {
+                    Some(start) => start,
+                    None => match try!(self.query(adapter, total)) {
+                        Step::Continue(start) => start,
+                        done => return Ok(done),
+                    },
+                };
+
+                self.send_chunk(adapter, &mut source, start, total)
+            });
+
+            match try!(step) {
+                Step::Continue(next) => offset = Some(next),
+                Step::Done(raw) => return Ok(raw),
+            }
+        }
+    }
+
+    fn query<A: AbsAdapter>(&self, adapter: &A, total: u64) -> Result<Step> {
+        match self.protocol {
+            UploadProtocol::ContentRange => {
+                let mut builder = RequestBuilder::new(adapter, Put, self.url.clone());
+                builder.head_mut()
+                    .header(ContentRange(ContentRangeSpec::Bytes { range: None, instance_length: Some(total) }))
+                    .header(ContentLength(0));
+
+                content_range_step(try!(builder.build().exec_here()))
+            },
+            UploadProtocol::Tus => {
+                let mut builder = RequestBuilder::new(adapter, Head, self.url.clone());
+                builder.head_mut().raw_header("Tus-Resumable", "1.0.0");
+
+                tus_step(try!(builder.build().exec_here()), total)
+            },
+        }
+    }
+
+    fn send_chunk<A, R>(&self, adapter: &A, source: &mut R, start: u64, total: u64) -> Result<Step>
+    where A: AbsAdapter, R: Read + Seek {
+        try!(source.seek(SeekFrom::Start(start)));
+
+        let mut chunk = Vec::new();
+        try!(source.take(self.chunk_size).read_to_end(&mut chunk));
+
+        if chunk.is_empty() {
+            return Err(resume_error(ResumeError::Offset));
+        }
+
+        let len = chunk.len() as u64;
+
+        match self.protocol {
+            UploadProtocol::ContentRange => {
+                let mut builder = RequestBuilder::new(adapter, Put, self.url.clone());
+                builder.head_mut()
+                    .header(ContentRange(ContentRangeSpec::Bytes {
+                        range: Some((start, start + len - 1)),
+                        instance_length: Some(total),
+                    }));
+
+                let body = RawBody::new(Cursor::new(chunk), mime::octet_stream()).with_content_length(len);
+                content_range_step(try!(builder.body(body).build().exec_here()))
+            },
+            UploadProtocol::Tus => {
+                let mut builder = RequestBuilder::new(adapter, Patch, self.url.clone());
+                builder.head_mut()
+                    .raw_header("Tus-Resumable", "1.0.0")
+                    .raw_header("Upload-Offset", start);
+
+                let body = RawBody::new(Cursor::new(chunk), mime!(Application/("offset+octet-stream")))
+                    .with_content_length(len);
+                tus_step(try!(builder.body(body).build().exec_here()), total)
+            },
+        }
+    }
+}
+
+enum Step {
+    Continue(u64),
+    Done(Raw),
+}
+
+fn content_range_step(raw: Raw) -> Result<Step> {
+    match raw.0.status {
+        StatusCode::Ok | StatusCode::Created => Ok(Step::Done(raw)),
+        // "Resume Incomplete"
+        StatusCode::PermanentRedirect => Ok(Step::Continue(received_range(&raw.0.headers))),
+        status => Err(resume_error(ResumeError::Status(status))),
+    }
+}
+
+fn tus_step(raw: Raw, total: u64) -> Result<Step> {
+    if !raw.0.status.is_success() {
+        return Err(resume_error(ResumeError::Status(raw.0.status)));
+    }
+
+    match upload_offset(&raw.0.headers) {
+        Some(offset) if offset == total => Ok(Step::Done(raw)),
+        Some(offset) if offset < total => Ok(Step::Continue(offset)),
+        _ => Err(resume_error(ResumeError::Offset)),
+    }
+}
+
+/// The number of bytes received according to the `Range` response header, 0 if not present.
+fn received_range(headers: &Headers) -> u64 {
+    match headers.get::<Range>() {
+        Some(&Range::Bytes(ref specs)) => match specs.first() {
+            Some(&ByteRangeSpec::FromTo(0, last)) => last + 1,
+            _ => 0,
+        },
+        _ => 0,
+    }
+}
+
+/// The `Upload-Offset` response header.
+fn upload_offset(headers: &Headers) -> Option<u64> {
+    headers.get_raw("Upload-Offset")
+        .and_then(|vals| vals.first())
+        .and_then(|val| str::from_utf8(val).ok())
+        .and_then(|val| val.trim().parse().ok())
+}
+
+/// How failed attempts of a transfer are retried.
+#[derive(Copy, Clone, Debug)]
+struct Retry {
+    attempts: u32,
+    initial_delay: Duration,
+    max_delay: Duration,
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Retry {
+            attempts: DEFAULT_ATTEMPTS,
+            initial_delay: Duration::from_millis(DEFAULT_INITIAL_DELAY_MS),
+            max_delay: Duration::from_millis(DEFAULT_MAX_DELAY_MS),
+        }
+    }
+}
+
+impl Retry {
+    /// Call `attempt` until it succeeds, fails with an error that is not retryable,
+    /// or fails `attempts` times in a row, sleeping between attempts.
+    ///
+    /// `progress` returns the position of the transfer; if a failed attempt advanced it,
+    /// the failure count and delay are reset.
+    fn run<T, P, F>(&self, mut progress: P, mut attempt: F) -> Result<T>
+    where P: FnMut() -> u64, F: FnMut() -> Result<T> {
+        let mut failures = 0;
+        let mut delay = self.initial_delay;
+        let mut position = progress();
+
+        loop {
+            match attempt() {
+                Err(e @ Error::Hyper(_)) | Err(e @ Error::StdIo(_)) => {
+                    let current = progress();
+
+                    if current > position {
+                        position = current;
+                        failures = 0;
+                        delay = self.initial_delay;
+                    }
+
+                    failures += 1;
+
+                    if failures >= self.attempts {
+                        return Err(e);
+                    }
+
+                    thread::sleep(delay);
+                    delay = ::std::cmp::min(delay * 2, self.max_delay);
+                },
+                res => return res,
+            }
+        }
+    }
+}
+
+fn resume_error(err: ResumeError) -> Error {
+    Error::Other(Box::new(err))
+}
+
+quick_error! {
+    /// Error returned by resumable transfers when the server does not respond as expected.
+    #[derive(Debug)]
+    pub enum ResumeError {
+        /// "The server responded with an unexpected status"
+        Status(status: StatusCode) {
+            description("The server responded with an unexpected status")
+            display("The server responded with an unexpected status: {}", status)
+        }
+        /// "The range or offset reported by the server is missing or does not match"
+        Offset {
+            description("The range or offset reported by the server is missing or does not match")
+        }
+    }
+}
+
+#[test]
+fn resume_offsets() {
+    let mut headers = Headers::new();
+    assert_eq!(received_range(&headers), 0);
+    assert_eq!(upload_offset(&headers), None);
+
+    headers.set_raw("Range", vec![b"bytes=0-1023".to_vec()]);
+    headers.set_raw("Upload-Offset", vec![b"2048".to_vec()]);
+    assert_eq!(received_range(&headers), 1024);
+    assert_eq!(upload_offset(&headers), Some(2048));
+
+    let retry = Retry {
+        attempts: 3,
+        initial_delay: Duration::from_millis(1),
+        max_delay: Duration::from_millis(2),
+    };
+
+    let mut calls = 0;
+    let res: Result<()> = retry.run(|| 0, || {
+        calls += 1;
+        Err(io::Error::new(io::ErrorKind::ConnectionReset, "reset").into())
+    });
+    assert!(res.is_err());
+    assert_eq!(calls, 3);
+
+    // Failures which make progress don't count towards the limit
+    let (position, mut calls) = (::std::cell::Cell::new(0), 0);
+    let res: Result<u64> = retry.run(|| position.get(), || {
+        calls += 1;
+        position.set(calls);
+
+        if calls < 10 {
+            Err(io::Error::new(io::ErrorKind::ConnectionReset, "reset").into())
+        } else {
+            Ok(calls)
+        }
+    });
+    assert_eq!(res.unwrap(), 10);
+}
@@ -0,0 +1,618 @@
+//! Server-Sent Events: consuming `text/event-stream` responses.
+//!
+//! Return `EventStream` from a service method to iterate over the events of a single response,
+//! or wrap the method in `EventSource` to reconnect automatically with `Last-Event-ID`.
+//!
+//! ```rust,no_run
+//! # #[macro_use] extern crate anterofit;
+//! use anterofit::Adapter;
+//! use anterofit::net::sse::{EventSource, EventStream};
+//!
+//! service! {
+//!     pub trait NotificationService {
+//!         fn notifications(&self, last_event_id: Option<String>) -> EventStream {
+//!             GET("/notifications");
+//!             event_stream!(last_event_id)
+//!         }
+//!     }
+//! }
+//!
+//! # fn main() {
+//! let adapter = Adapter::builder()
+//!     .base_url("https://example.com".parse().unwrap())
+//!     .build();
+//!
+//! let events = EventSource::new(|last_event_id| {
+//!     adapter.notifications(last_event_id.map(Into::into)).exec_here()
+//! });
+//!
+//! for event in events {
+//!     let event = event.unwrap();
+//!     println!("{}: {}", event.event, event.data);
+//! }
+//! # }
+//! ```
+
+use futures::{Async, Poll, Stream};
+use futures::sync::mpsc::{self, UnboundedReceiver};
+
+use hyper::header::{CacheControl, CacheDirective, ContentType};
+use hyper::status::StatusCode;
+
+use mime::{self, Mime};
+
+use std::io::{self, BufRead, BufReader, Read};
+use std::mem;
+use std::thread;
+use std::time::Duration;
+
+use net::request::RequestHead;
+use net::response::{FromResponse, Response};
+
+use serialize::Deserializer;
+
+use ::{Error, Result};
+
+const DEFAULT_RETRY_MS: u64 = 3000;
+
+const DEFAULT_ATTEMPTS: u32 = 5;
+
+// How long the reader thread of `EventSource::spawn()` waits for data before checking if
+// `Events` was dropped
+const CANCEL_CHECK_MS: u64 = 500;
+
+/// An event parsed from a `text/event-stream` body.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Event {
+    /// The event type, `message` if not given.
+    pub event: String,
+    /// The event data; the lines of multiple `data` fields are joined with `\n`.
+    pub data: String,
+    /// The last event ID seen in the stream, if any.
+    pub id: Option<String>,
+    /// The reconnection time, if given with this event.
+    pub retry: Option<Duration>,
+}
+
+/// Set the headers for a request for an event stream: `Accept: text/event-stream`,
+/// `Cache-Control: no-cache` and `Last-Event-ID` if given.
+///
+/// Used by `event_stream!()`.
+pub fn prepare_request<I: AsRef<str>>(head: &mut RequestHead, last_event_id: Option<I>) {
+    head.raw_header("Accept", mime::event_stream())
+        .header(CacheControl(vec![CacheDirective::NoCache]));
+
+    if let Some(id) = last_event_id {
+        head.raw_header("Last-Event-ID", id.as_ref());
+    }
+}
+
+/// An iterator over the events in a `text/event-stream` body.
+///
+/// Use this as a service method return type. Reading blocks until the next event arrives.
+/// An event which is not terminated by a blank line when the stream ends is discarded.
+#[derive(Debug)]
+pub struct EventStream<R = Response> {
+    reader: BufReader<R>,
+    last_event_id: Option<String>,
+    retry: Option<Duration>,
+    started: bool,
+    // Set after `\r` so a following `\n` is not taken as an empty line
+    skip_lf: bool,
+    // The partially read line and event, kept so a timed out read can be resumed
+    line: Vec<u8>,
+    event: String,
+    data: String,
+    event_retry: Option<Duration>,
+}
+
+impl<R: Read> EventStream<R> {
+    /// Parse events from `read`.
+    pub fn new(read: R) -> Self {
+        EventStream {
+            reader: BufReader::new(read),
+            last_event_id: None,
+            retry: None,
+            started: false,
+            skip_lf: false,
+            line: Vec::new(),
+            event: String::new(),
+            data: String::new(),
+            event_retry: None,
+        }
+    }
+
+    /// Get the last event ID seen in the stream.
+    pub fn last_event_id(&self) -> Option<&str> {
+        self.last_event_id.as_deref()
+    }
+
+    /// Get the last reconnection time given by the stream.
+    pub fn retry(&self) -> Option<Duration> {
+        self.retry
+    }
+
+    fn next_event(&mut self) -> io::Result<Option<Event>> {
+        loop {
+            if !try!(self.read_line()) {
+                return Ok(None);
+            }
+
+            let mut line = mem::replace(&mut self.line, Vec::new());
+
+            if !self.started {
+                self.started = true;
+
+                if line.starts_with(b"\xEF\xBB\xBF") {
+                    line.drain(..3);
+                }
+            }
+
+            if line.is_empty() {
+                if self.data.is_empty() {
+                    self.event.clear();
+                    continue;
+                }
+
+                let event = mem::replace(&mut self.event, String::new());
+                let mut data = mem::replace(&mut self.data, String::new());
+                data.pop();
+
+                return Ok(Some(Event {
+                    event: if event.is_empty() { "message".to_owned() } else { event },
+                    data: data,
+                    id: self.last_event_id.clone(),
+                    retry: self.event_retry.take(),
+                }));
+            }
+
+            let line = String::from_utf8_lossy(&line);
+
+            let (field, value) = match line.find(':') {
+                // Comment
+                Some(0) => continue,
+                Some(idx) => {
+                    let value = &line[idx + 1..];
+                    (&line[..idx], value.strip_prefix(' ').unwrap_or(value))
+                },
+                None => (&*line, ""),
+            };
+
+            match field {
+                "event" => self.event = value.to_owned(),
+                "data" => {
+                    self.data.push_str(value);
+                    self.data.push('\n');
+                },
+                "id" if !value.contains('\0') =>
+                    self.last_event_id = if value.is_empty() { None } else { Some(value.to_owned()) },
+                "retry" if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) => {
+                    if let Ok(ms) = value.parse() {
+                        self.event_retry = Some(Duration::from_millis(ms));
+                        self.retry = self.event_retry;
+                    }
+                },
+                _ => (),
+            }
+        }
+    }
+
+    /// Read a line ended by `\r\n`, `\n` or `\r`, returning `false` at the end of the stream.
+    fn read_line(&mut self) -> io::Result<bool> {
+        loop {
+            let (done, used) = {
+                let buf = try!(self.reader.fill_buf());
+
+                if buf.is_empty() {
+                    return Ok(false);
+                }
+
+                let start = if self.skip_lf && buf[0] == b'\n' { 1 } else { 0 };
+                self.skip_lf = false;
+
+                match buf[start..].iter().position(|&b| b == b'\n' || b == b'\r') {
+                    Some(pos) => {
+                        self.line.extend_from_slice(&buf[start .. start + pos]);
+                        self.skip_lf = buf[start + pos] == b'\r';
+                        (true, start + pos + 1)
+                    },
+                    None => {
+                        self.line.extend_from_slice(&buf[start..]);
+                        (false, buf.len())
+                    }
+                }
+            };
+
+            self.reader.consume(used);
+
+            if done {
+                return Ok(true);
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for EventStream<R> {
+    type Item = Result<Event>;
+
+    fn next(&mut self) -> Option<Result<Event>> {
+        match self.next_event() {
+            Ok(event) => event.map(Ok),
+            Err(e) => Some(Err(e.into())),
+        }
+    }
+}
+
+impl EventStream {
+    /// Set the read timeout of the underlying connection.
+    ///
+    /// A read which times out returns an error without losing the partially read event,
+    /// so iteration can continue afterwards.
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.reader.get_ref().get_ref().set_read_timeout(timeout)
+    }
+}
+
+impl FromResponse for EventStream {
+    /// Fails with `SseError::Status` if the response status is not `200 OK`, or with
+    /// `SseError::ContentType` if the response has a `Content-Type` other than
+    /// `text/event-stream`.
+    fn from_response<D>(_des: &D, response: Response) -> Result<Self>
+        where D: Deserializer {
+        if response.status != StatusCode::Ok {
+            return Err(Error::Other(Box::new(SseError::Status(response.status))));
+        }
+
+        let event_stream = mime::event_stream();
+
+        if let Some(&ContentType(ref content_type)) = response.headers.get::<ContentType>() {
+            if content_type.0 != event_stream.0 || content_type.1 != event_stream.1 {
+                return Err(Error::deserialize(SseError::ContentType(content_type.clone())));
+            }
+        }
+
+        Ok(EventStream::new(response))
+    }
+}
+
+/// An iterator over the events of a stream which reconnects when the stream ends or fails,
+/// sending the last event ID seen.
+///
+/// After failing to connect for the configured number of consecutive attempts, the error is
+/// returned and the iterator ends. Responses with a status other than `200 OK` count as failed
+/// attempts, except `204 No Content`, which tells the client to stop reconnecting and ends the
+/// iterator without an error.
+pub struct EventSource<F, R = Response> {
+    connect: F,
+    stream: Option<EventStream<R>>,
+    last_event_id: Option<String>,
+    retry: Duration,
+    attempts: u32,
+    connected: bool,
+    closed: bool,
+    // Return read timeouts instead of reconnecting; set by `spawn()`
+    yield_timeouts: bool,
+}
+
+impl<F, R: Read> EventSource<F, R> where F: FnMut(Option<&str>) -> Result<EventStream<R>> {
+    /// Create an event source which connects by calling `connect` with the last event ID.
+    pub fn new(connect: F) -> Self {
+        EventSource {
+            connect: connect,
+            stream: None,
+            last_event_id: None,
+            retry: Duration::from_millis(DEFAULT_RETRY_MS),
+            attempts: DEFAULT_ATTEMPTS,
+            connected: false,
+            closed: false,
+            yield_timeouts: false,
+        }
+    }
+
+    /// Set the time to wait before reconnecting, until the stream gives one. Defaults to 3 seconds.
+    pub fn retry(self, retry: Duration) -> Self {
+        EventSource { retry: retry, .. self }
+    }
+
+    /// Set the number of consecutive failed connection attempts before giving up.
+    /// Defaults to 5.
+    pub fn attempts(self, attempts: u32) -> Self {
+        EventSource { attempts: attempts, .. self }
+    }
+
+    /// Set the last event ID to send when first connecting.
+    pub fn last_event_id<I: Into<String>>(self, last_event_id: I) -> Self {
+        EventSource { last_event_id: Some(last_event_id.into()), .. self }
+    }
+
+    fn connect(&mut self) -> Result<EventStream<R>> {
+        let mut failures = 0;
+
+        loop {
+            if self.connected || failures > 0 {
+                thread::sleep(self.retry);
+            }
+
+            match (self.connect)(self.last_event_id.as_deref()) {
+                Ok(mut stream) => {
+                    // The stream may reset the ID
+                    stream.last_event_id = self.last_event_id.clone();
+                    self.connected = true;
+                    return Ok(stream);
+                },
+                Err(e) => {
+                    failures += 1;
+
+                    if failures >= self.attempts || is_no_content(&e) {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<F, R: Read> Iterator for EventSource<F, R> where F: FnMut(Option<&str>) -> Result<EventStream<R>> {
+    type Item = Result<Event>;
+
+    fn next(&mut self) -> Option<Result<Event>> {
+        while !self.closed {
+            let mut stream = match self.stream.take() {
+                Some(stream) => stream,
+                None => match self.connect() {
+                    Ok(stream) => stream,
+                    Err(ref e) if is_no_content(e) => {
+                        self.closed = true;
+                        return None;
+                    },
+                    Err(e) => {
+                        self.closed = true;
+                        return Some(Err(e));
+                    }
+                },
+            };
+
+            let next = stream.next();
+
+            if self.yield_timeouts {
+                if let Some(Err(ref e)) = next {
+                    if is_timeout(e) {
+                        self.stream = Some(stream);
+                        return next;
+                    }
+                }
+            }
+
+            self.last_event_id = stream.last_event_id.clone();
+
+            if let Some(retry) = stream.retry {
+                self.retry = retry;
+            }
+
+            // Reconnect if the stream ended or failed
+            if let Some(Ok(event)) = next {
+                self.stream = Some(stream);
+                return Some(Ok(event));
+            }
+        }
+
+        None
+    }
+}
+
+impl<F> EventSource<F> where F: FnMut(Option<&str>) -> Result<EventStream> + Send + 'static {
+    /// Read events on a new thread, returning them as a `futures::Stream`.
+    ///
+    /// The thread is not run on the adapter's executor, so it does not hold up other requests
+    /// or `Adapter::shutdown()`. It exits when the source ends, or once `Events` is dropped:
+    /// the thread checks for this after each event, and at least every half second while the
+    /// connection is idle. While waiting to reconnect, it checks after the wait.
+    pub fn spawn(self) -> Events {
+        let (tx, rx) = mpsc::unbounded();
+
+        let mut connect = self.connect;
+
+        let source = EventSource {
+            connect: move |last_event_id: Option<&str>| {
+                let stream = try!(connect(last_event_id));
+                try!(stream.set_read_timeout(Some(Duration::from_millis(CANCEL_CHECK_MS))));
+                Ok(stream)
+            },
+            stream: None,
+            last_event_id: self.last_event_id,
+            retry: self.retry,
+            attempts: self.attempts,
+            connected: false,
+            closed: false,
+            yield_timeouts: true,
+        };
+
+        thread::spawn(move || {
+            for event in source {
+                let cancelled = match event {
+                    Err(ref e) if is_timeout(e) => tx.is_closed(),
+                    event => tx.unbounded_send(event).is_err(),
+                };
+
+                if cancelled { break; }
+            }
+        });
+
+        Events(rx)
+    }
+}
+
+fn is_timeout(err: &Error) -> bool {
+    match *err {
+        Error::StdIo(ref e) => e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut,
+        _ => false,
+    }
+}
+
+fn is_no_content(err: &Error) -> bool {
+    if let Error::Other(ref e) = *err {
+        if let Some(&SseError::Status(StatusCode::NoContent)) = e.downcast_ref::<SseError>() {
+            return true;
+        }
+    }
+
+    false
+}
+
+/// The events of an `EventSource` read on another thread, as a `futures::Stream`.
+///
+/// Dropping this stops the thread.
+pub struct Events(UnboundedReceiver<Result<Event>>);
+
+impl Stream for Events {
+    type Item = Event;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<Event>, Error> {
+        match self.0.poll() {
+            Ok(Async::Ready(Some(Ok(event)))) => Ok(Async::Ready(Some(event))),
+            Ok(Async::Ready(Some(Err(e)))) => Err(e),
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            Ok(Async::Ready(None)) | Err(()) => Ok(Async::Ready(None)),
+        }
+    }
+}
+
+quick_error! {
+    /// Error returned by `EventStream` when a response is not an event stream.
+    #[derive(Debug)]
+    pub enum SseError {
+        /// The response status is not `200 OK`.
+        ///
+        /// `204 No Content` tells the client to stop reconnecting.
+        Status(status: StatusCode) {
+            description("The response status is not `200 OK`")
+            display("The response status is not `200 OK`: {}", status)
+        }
+        /// "The response's `Content-Type` is not `text/event-stream`"
+        ContentType(content_type: Mime) {
+            description("The response's `Content-Type` is not `text/event-stream`")
+            display("The response's `Content-Type` is not `text/event-stream`: {}", content_type)
+        }
+    }
+}
+
+#[test]
+fn parse_event_stream() {
+    let body = "\u{FEFF}: comment\r\nretry: 1000\r\ndata: first\r\ndata:second\r\n\r\n\
+                event: update\rid: 7\rdata\r\r\
+                data: no id\n\n\
+                id\nevent: empty\n\n\
+                data: incomplete";
+
+    let events: Vec<Event> = EventStream::new(body.as_bytes()).map(Result::unwrap).collect();
+
+    assert_eq!(events, [
+        Event {
+            event: "message".into(),
+            data: "first\nsecond".into(),
+            id: None,
+            retry: Some(Duration::from_millis(1000)),
+        },
+        Event { event: "update".into(), data: "".into(), id: Some("7".into()), retry: None },
+        Event { event: "message".into(), data: "no id".into(), id: Some("7".into()), retry: None },
+    ]);
+}
+
+#[test]
+fn event_source_reconnects() {
+    let mut sent_ids = Vec::new();
+    let mut bodies = vec!["id: 1\ndata: a\n\nid: 2\ndata: b\n\ndata: lost", "data: c\n\n"].into_iter();
+
+    let data: Vec<String> = {
+        let source = EventSource::new(|last_event_id: Option<&str>| {
+            sent_ids.push(last_event_id.map(String::from));
+            bodies.next().map(|body| EventStream::new(body.as_bytes()))
+                .ok_or_else(|| Error::from(io::Error::new(io::ErrorKind::ConnectionRefused, "closed")))
+        }).retry(Duration::from_millis(0)).attempts(2);
+
+        source.map(|event| event.map(|event| event.data).unwrap_or_else(|_| "error".into())).collect()
+    };
+
+    assert_eq!(data, ["a", "b", "c", "error"]);
+    assert_eq!(sent_ids, [None, Some("2".into()), Some("2".into()), Some("2".into())]);
+}
+
+#[test]
+fn event_source_status() {
+    use net::response::mock_response;
+    use serialize::none::NoDeserializer;
+
+    let response = |raw| EventStream::from_response(&NoDeserializer, mock_response(raw));
+
+    match response("HTTP/1.1 401 Unauthorized\r\n\r\n") {
+        Err(Error::Other(ref e)) => match e.downcast_ref::<SseError>() {
+            Some(&SseError::Status(StatusCode::Unauthorized)) => (),
+            _ => panic!("expected `SseError::Status`, got {:?}", e),
+        },
+        res => panic!("expected `SseError::Status`, got {:?}", res.map(|_| ())),
+    }
+
+    // A failed attempt; the next connection closes the source without an error
+    let mut responses = vec![
+        "HTTP/1.1 500 Internal Server Error\r\n\r\n",
+        "HTTP/1.1 200 OK\r\nContent-Length: 9\r\n\r\ndata: a\n\n",
+        "HTTP/1.1 204 No Content\r\n\r\n",
+    ].into_iter();
+
+    let mut attempts = 0;
+
+    let data: Vec<String> = EventSource::new(|_: Option<&str>| {
+        attempts += 1;
+        response(responses.next().unwrap())
+    }).retry(Duration::from_millis(0)).map(|event| event.unwrap().data).collect();
+
+    assert_eq!(data, ["a"]);
+    assert_eq!(attempts, 3);
+}
+
+#[test]
+fn events_dropped_stops_reader() {
+    use adapter::Adapter;
+    use futures::Stream;
+    use net::method::Get;
+    use net::request::RequestBuilder;
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::sync::mpsc as std_mpsc;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (closed_tx, closed) = std_mpsc::channel();
+
+    // Sends one event and keeps the connection open until the client closes it
+    let server = thread::spawn(move || {
+        let mut stream = BufReader::new(listener.accept().unwrap().0);
+        let mut line = String::new();
+
+        while stream.read_line(&mut line).unwrap() > 2 {
+            line.clear();
+        }
+
+        stream.get_mut().write_all(b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\
+                                     Transfer-Encoding: chunked\r\n\r\n9\r\ndata: a\n\n\r\n").unwrap();
+
+        let _ = stream.read_to_end(&mut Vec::new());
+        closed_tx.send(()).unwrap();
+    });
+
+    let adapter = Adapter::builder()
+        .base_url(format!("http://127.0.0.1:{}/", port).parse().unwrap())
+        .build();
+
+    let mut events = EventSource::new(move |_: Option<&str>| {
+        RequestBuilder::new(&adapter, Get, "events".into()).build::<EventStream>().exec_here()
+    }).spawn().wait();
+
+    assert_eq!(events.next().unwrap().unwrap().data, "a");
+    drop(events);
+
+    // The reader thread drops the connection when it exits
+    closed.recv_timeout(Duration::from_secs(5)).expect("the reader thread did not exit");
+    server.join().unwrap();
+}
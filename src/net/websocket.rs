@@ -0,0 +1,311 @@
+//! WebSocket connections opened from service methods. Requires the `websocket` feature.
+//!
+//! Return `WebSocket` from a service method to upgrade the request to a WebSocket connection
+//! instead of sending it. The URL is joined to the adapter's base URL with `http` and `https`
+//! replaced by `ws` and `wss`, the adapter's interceptor is applied, and the connection is opened
+//! with the adapter's connector, so authentication headers and TLS settings are shared with the
+//! other methods of the service. The request body, if any, is not sent.
+//!
+//! Messages are encoded and decoded with the serializer and deserializer of the request. They
+//! are sent as text frames if the serializer's content type is textual, binary frames otherwise.
+//!
+//! ```rust,no_run
+//! # #[macro_use] extern crate anterofit;
+//! # #[macro_use] extern crate serde_derive;
+//! use anterofit::Adapter;
+//! use anterofit::net::websocket::WebSocket;
+//!
+//! #[derive(Serialize)]
+//! pub struct Subscribe {
+//!     pub channel: String,
+//! }
+//!
+//! #[derive(Deserialize)]
+//! pub struct Tick {
+//!     pub price: f64,
+//! }
+//!
+//! service! {
+//!     pub trait TickerService {
+//!         fn ticker(&self) -> WebSocket<Subscribe, Tick> {
+//!             GET("/ticker")
+//!         }
+//!     }
+//! }
+//!
+//! # fn main() {
+//! let adapter = Adapter::builder()
+//!     .base_url("https://example.com".parse().unwrap())
+//!     .serialize_json()
+//!     .build();
+//!
+//! let mut ticker = adapter.ticker().exec_here().unwrap();
+//! ticker.send(&Subscribe { channel: "BTC".into() }).unwrap();
+//!
+//! while let Some(tick) = ticker.recv().unwrap() {
+//!     println!("{}", tick.price);
+//! }
+//! # }
+//! ```
+
+use hyper::net::NetworkStream;
+
+use mime_::{Mime, SubLevel, TopLevel};
+
+use tungstenite::{self, Message};
+use tungstenite::client::IntoClientRequest;
+use tungstenite::handshake::HandshakeError;
+use tungstenite::http::header::{HeaderName, HeaderValue};
+
+use std::fmt;
+use std::io;
+
+use net::connect::Upgrade;
+use net::response::{FromResponse, Response};
+
+use serialize::{Serialize, Deserialize, Serializer, Deserializer};
+
+use ::{Error, Result};
+
+/// The underlying connection of a `WebSocket`.
+pub type Socket = tungstenite::WebSocket<Box<NetworkStream + Send>>;
+
+/// A WebSocket connection which sends messages of type `Out` and receives messages of type `In`.
+///
+/// Use this as a service method return type to open the connection.
+pub struct WebSocket<Out, In = Out> {
+    socket: Socket,
+    codec: Box<Codec<Out, In>>,
+}
+
+impl<Out, In> WebSocket<Out, In> {
+    /// Serialize `msg` and send it.
+    pub fn send(&mut self, msg: &Out) -> Result<()> {
+        let msg = try!(self.codec.encode(msg));
+        self.socket.send(msg).map_err(ws_error)
+    }
+
+    /// Receive and deserialize the next message, or `None` if the connection was closed.
+    ///
+    /// Ping and pong frames are handled automatically and skipped.
+    pub fn recv(&mut self) -> Result<Option<In>> {
+        loop {
+            match self.socket.read() {
+                Ok(msg @ Message::Text(_)) | Ok(msg @ Message::Binary(_)) =>
+                    return self.codec.decode(msg).map(Some),
+                Ok(Message::Close(_)) |
+                Err(tungstenite::Error::ConnectionClosed) |
+                Err(tungstenite::Error::AlreadyClosed) => return Ok(None),
+                Ok(_) => (),
+                Err(e) => return Err(ws_error(e)),
+            }
+        }
+    }
+
+    /// Start the closing handshake.
+    ///
+    /// Call `recv()` until it returns `None` to complete it.
+    pub fn close(&mut self) -> Result<()> {
+        match self.socket.close(None) {
+            Ok(()) | Err(tungstenite::Error::ConnectionClosed) => Ok(()),
+            Err(e) => Err(ws_error(e)),
+        }
+    }
+
+    /// Get a reference to the underlying connection.
+    pub fn get_ref(&self) -> &Socket {
+        &self.socket
+    }
+
+    /// Get a mutable reference to the underlying connection, to send or receive raw messages.
+    pub fn get_mut(&mut self) -> &mut Socket {
+        &mut self.socket
+    }
+}
+
+impl<Out, In> FromResponse for WebSocket<Out, In>
+where Out: Serialize + 'static, In: Deserialize + 'static {
+    /// Always returns an error; WebSocket connections are opened by `from_upgrade()`.
+    fn from_response<D>(_des: &D, _response: Response) -> Result<Self>
+        where D: Deserializer {
+        Err(io::Error::new(io::ErrorKind::InvalidInput, "WebSocket connections must be upgraded").into())
+    }
+
+    const UPGRADE: bool = true;
+
+    fn from_upgrade<C>(upgrade: Upgrade, codec: C) -> Result<Self>
+        where C: Serializer + Deserializer {
+        let mut url = try!(upgrade.url());
+
+        let (ws_scheme, scheme) = match url.scheme() {
+            "http" | "ws" => ("ws", "http"),
+            "https" | "wss" => ("wss", "https"),
+            other => return Err(Error::Other(Box::new(WebSocketError::Scheme(other.into())))),
+        };
+
+        let _ = url.set_scheme(ws_scheme);
+
+        let stream = {
+            let host = try!(url.host_str().ok_or(::url::ParseError::EmptyHost));
+            let port = url.port_or_known_default().unwrap_or(if scheme == "https" { 443 } else { 80 });
+            try!(upgrade.connector.connect(host, port, scheme))
+        };
+
+        let mut request = try!(url.into_string().into_client_request().map_err(ws_error));
+
+        for header in upgrade.head.get_headers().iter() {
+            let name = try!(HeaderName::from_bytes(header.name().as_bytes()).map_err(boxed));
+            let value = try!(HeaderValue::from_str(&header.value_string()).map_err(boxed));
+            request.headers_mut().insert(name, value);
+        }
+
+        let socket = match tungstenite::client(request, stream) {
+            Ok((socket, _)) => socket,
+            Err(HandshakeError::Failure(e)) => return Err(ws_error(e)),
+            // Only returned for nonblocking streams
+            Err(HandshakeError::Interrupted(_)) => return Err(io::Error::from(io::ErrorKind::WouldBlock).into()),
+        };
+
+        let text = match codec.content_type() {
+            Some(content_type) => is_text(&content_type),
+            None => false,
+        };
+
+        Ok(WebSocket {
+            socket: socket,
+            codec: Box::new(MessageCodec {
+                codec: codec,
+                text: text,
+            }),
+        })
+    }
+}
+
+impl<Out, In> fmt::Debug for WebSocket<Out, In> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("WebSocket")
+            .field("can_read", &self.socket.can_read())
+            .field("can_write", &self.socket.can_write())
+            .finish()
+    }
+}
+
+/// Converts between values and messages; object-safe so `WebSocket` doesn't carry the
+/// (de)serializer types.
+trait Codec<Out, In>: Send {
+    fn encode(&self, msg: &Out) -> Result<Message>;
+
+    fn decode(&self, msg: Message) -> Result<In>;
+}
+
+struct MessageCodec<C> {
+    codec: C,
+    text: bool,
+}
+
+impl<C, Out, In> Codec<Out, In> for MessageCodec<C>
+where C: Serializer + Deserializer, Out: Serialize, In: Deserialize {
+    fn encode(&self, msg: &Out) -> Result<Message> {
+        let mut buf = Vec::new();
+        try!(Serializer::serialize(&self.codec, msg, &mut buf));
+
+        if self.text {
+            Error::map_serialize(String::from_utf8(buf)).map(Message::Text)
+        } else {
+            Ok(Message::Binary(buf))
+        }
+    }
+
+    fn decode(&self, msg: Message) -> Result<In> {
+        Deserializer::deserialize(&self.codec, &mut &*msg.into_data())
+    }
+}
+
+/// If messages with this content type should be sent as text frames.
+fn is_text(content_type: &Mime) -> bool {
+    match *content_type {
+        Mime(TopLevel::Text, _, _) | Mime(_, SubLevel::Json, _) | Mime(_, SubLevel::Xml, _) => true,
+        Mime(_, SubLevel::Ext(ref sub), _) => sub.ends_with("+json") || sub.ends_with("+xml"),
+        _ => false,
+    }
+}
+
+fn ws_error(err: tungstenite::Error) -> Error {
+    Error::Other(Box::new(err))
+}
+
+fn boxed<E: ::std::error::Error + Send + 'static>(err: E) -> Error {
+    Error::Other(Box::new(err))
+}
+
+quick_error! {
+    /// Error returned when opening a `WebSocket` fails before connecting.
+    #[derive(Debug)]
+    pub enum WebSocketError {
+        /// The URL of the request does not have an `http`, `https`, `ws` or `wss` scheme.
+        Scheme(scheme: String) {
+            description("The URL of the request does not have a WebSocket or HTTP scheme")
+            display("The URL of the request does not have a WebSocket or HTTP scheme: {}", scheme)
+        }
+    }
+}
+
+#[cfg(feature = "serde_json")]
+#[test]
+fn websocket_echo() {
+    use adapter::Adapter;
+    use hyper::header::Authorization;
+    use net::intercept::AddHeader;
+    use net::method::Get;
+    use net::request::RequestBuilder;
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+    use tungstenite::handshake::server::{Callback, ErrorResponse, Request, Response as ServerResponse};
+
+    // Sends the request URI and `Authorization` header of the handshake
+    struct Handshake(mpsc::Sender<(String, Option<String>)>);
+
+    impl Callback for Handshake {
+        fn on_request(self, req: &Request, res: ServerResponse) -> ::std::result::Result<ServerResponse, ErrorResponse> {
+            let auth = req.headers().get("Authorization").map(|auth| auth.to_str().unwrap().to_owned());
+            self.0.send((req.uri().to_string(), auth)).unwrap();
+            Ok(res)
+        }
+    }
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (tx, handshake) = mpsc::channel();
+
+    let server = thread::spawn(move || {
+        let stream = listener.accept().unwrap().0;
+        let mut socket = tungstenite::accept_hdr(stream, Handshake(tx)).unwrap();
+
+        // Echo until the client closes the connection
+        while let Ok(msg) = socket.read() {
+            if msg.is_text() || msg.is_binary() {
+                socket.send(msg).unwrap();
+            }
+        }
+    });
+
+    let adapter = Adapter::builder()
+        .base_url(format!("http://127.0.0.1:{}/api/", port).parse().unwrap())
+        .interceptor(AddHeader(Authorization("token".to_owned())))
+        .serialize_json()
+        .build();
+
+    let mut socket = RequestBuilder::new(&adapter, Get, "echo".into())
+        .build::<WebSocket<Vec<u32>>>().exec_here().unwrap();
+
+    assert_eq!(handshake.recv().unwrap(), ("/api/echo?".to_owned(), Some("token".to_owned())));
+
+    socket.send(&vec![1, 2, 3]).unwrap();
+    assert_eq!(socket.recv().unwrap(), Some(vec![1, 2, 3]));
+
+    socket.close().unwrap();
+    assert_eq!(socket.recv().unwrap(), None);
+
+    server.join().unwrap();
+}
@@ -0,0 +1,54 @@
+//! Integration with the `serde_cbor` crate providing CBOR serialization.
+
+extern crate serde_cbor;
+
+use mime::{self, Mime};
+
+use std::io::{Read, Write};
+
+use super::{Serialize, Deserialize};
+
+use serialize;
+use ::{Error, Result};
+
+/// Serializer for CBOR request bodies.
+#[derive(Clone, Debug, Default)]
+pub struct Serializer;
+
+impl serialize::Serializer for Serializer {
+    fn serialize<T: Serialize, W: Write>(&self, val: &T, write: &mut W) -> Result<()> {
+        Error::map_serialize(self::serde_cbor::to_writer(write, val))
+    }
+
+    /// Returns `application/cbor`.
+    fn content_type(&self) -> Option<Mime> {
+        Some(mime::cbor())
+    }
+}
+
+/// Deserializer for pulling values from CBOR response bodies.
+#[derive(Clone, Debug, Default)]
+pub struct Deserializer;
+
+impl serialize::Deserializer for Deserializer {
+    fn deserialize<T: Deserialize, R: Read>(&self, read: &mut R) -> Result<T> {
+        Error::map_deserialize(self::serde_cbor::from_reader(read))
+    }
+}
+
+#[test]
+fn cbor_round_trip() {
+    use std::collections::BTreeMap;
+    use serialize::{Serializer as Ser, Deserializer as De};
+
+    let mut map = BTreeMap::new();
+    map.insert("answer".to_string(), vec![4, 2]);
+
+    let mut buf = Vec::new();
+    Serializer.serialize(&map, &mut buf).unwrap();
+    // A map of one entry, keyed by a text string of 6 bytes
+    assert_eq!(&buf[..2], &[0xA1, 0x66]);
+
+    let parsed: BTreeMap<String, Vec<u8>> = Deserializer.deserialize(&mut &buf[..]).unwrap();
+    assert_eq!(parsed, map);
+}
@@ -0,0 +1,214 @@
+//! Unwrapping of values from response envelopes, such as `{"data": ..., "meta": ..., "links": ...}`.
+//!
+//! Use `Unwrap` as the adapter's deserializer to unwrap every response, or `Document` as a
+//! service method return type to also get the metadata and links.
+//!
+//! ```rust
+//! # #[macro_use] extern crate anterofit;
+//! # #[macro_use] extern crate serde_derive;
+//! use anterofit::Adapter;
+//! use anterofit::serialize::json;
+//! use anterofit::serialize::envelope::{Document, Unwrap};
+//!
+//! #[derive(Deserialize)]
+//! pub struct User {
+//!     pub name: String,
+//! }
+//!
+//! #[derive(Deserialize)]
+//! pub struct Page {
+//!     pub total: u64,
+//! }
+//!
+//! service! {
+//!     pub trait UserService {
+//!         // `{"data": {"name": ...}}`
+//!         fn user(&self, id: u64) -> User {
+//!             GET("/users/{id}")
+//!         }
+//!
+//!         // `{"data": [...], "meta": {"total": ...}}`
+//!         fn users(&self) -> Document<Vec<User>, Page> {
+//!             GET("/users");
+//!             // Bypass `Unwrap` to get the whole document
+//!             deserializer!(json::Deserializer)
+//!         }
+//!     }
+//! }
+//!
+//! # fn main() {
+//! let adapter = Adapter::builder()
+//!     .serializer(json::Serializer)
+//!     .deserializer(Unwrap::new(json::Deserializer))
+//!     .build();
+//! # }
+//! ```
+
+use serde::de::{self, IgnoredAny, MapAccess, Visitor};
+
+use mime::Mime;
+
+use std::fmt;
+use std::io::Read;
+use std::marker::PhantomData;
+
+use net::response::Response;
+
+use super::{Deserializer, Deserialize};
+
+use ::Result;
+
+/// Names the keys of an envelope.
+///
+/// Implement this on your own type for envelopes with other keys.
+pub trait Envelope: Send + Sync + 'static {
+    /// The key of the wrapped value.
+    const DATA: &'static str;
+    /// The key of the metadata, if any.
+    const META: &'static str = "meta";
+    /// The key of the links, if any.
+    const LINKS: &'static str = "links";
+}
+
+/// JSON:API envelopes: `{"data": ..., "meta": ..., "links": ...}`
+#[derive(Copy, Clone, Debug, Default)]
+pub struct JsonApi;
+
+impl Envelope for JsonApi {
+    const DATA: &'static str = "data";
+}
+
+/// HAL envelopes: `{"_embedded": ..., "_links": ...}`
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Hal;
+
+impl Envelope for Hal {
+    const DATA: &'static str = "_embedded";
+    const LINKS: &'static str = "_links";
+}
+
+/// A deserializer which unwraps responses from an envelope before handing them to `T`.
+///
+/// Responses which are not a map containing the `E::DATA` key fail to deserialize.
+#[derive(Clone, Debug, Default)]
+pub struct Unwrap<D, E = JsonApi> {
+    inner: D,
+    _envelope: PhantomData<E>,
+}
+
+impl<D> Unwrap<D> {
+    /// Wrap `inner` to unwrap JSON:API envelopes.
+    pub fn new(inner: D) -> Self {
+        Unwrap::with_envelope(inner)
+    }
+}
+
+impl<D, E> Unwrap<D, E> {
+    /// Wrap `inner` to unwrap envelopes with the keys named by `E`.
+    pub fn with_envelope(inner: D) -> Self {
+        Unwrap {
+            inner: inner,
+            _envelope: PhantomData,
+        }
+    }
+}
+
+impl<D: Deserializer, E: Envelope> Deserializer for Unwrap<D, E> {
+    fn deserialize<T: Deserialize, R: Read>(&self, read: &mut R) -> Result<T> {
+        self.inner.deserialize::<Document<T, IgnoredAny, IgnoredAny, E>, _>(read)
+            .map(|doc| doc.data)
+    }
+
+    fn deserialize_response<T: Deserialize>(&self, response: &mut Response) -> Result<T> {
+        self.inner.deserialize_response::<Document<T, IgnoredAny, IgnoredAny, E>>(response)
+            .map(|doc| doc.data)
+    }
+
+    fn accept(&self) -> Vec<Mime> {
+        self.inner.accept()
+    }
+}
+
+/// A value along with the metadata and links from its envelope.
+///
+/// Use as a service method return type with a deserializer that does not already unwrap
+/// responses.
+#[derive(Debug)]
+pub struct Document<T, M = IgnoredAny, L = IgnoredAny, E = JsonApi> {
+    /// The value under `E::DATA`.
+    pub data: T,
+    /// The value under `E::META`, if present.
+    pub meta: Option<M>,
+    /// The value under `E::LINKS`, if present.
+    pub links: Option<L>,
+    _envelope: PhantomData<E>,
+}
+
+impl<'de, T, M, L, E> de::Deserialize<'de> for Document<T, M, L, E>
+where T: de::Deserialize<'de>, M: de::Deserialize<'de>, L: de::Deserialize<'de>, E: Envelope {
+    fn deserialize<D_>(deserializer: D_) -> ::std::result::Result<Self, D_::Error>
+    where D_: de::Deserializer<'de> {
+        deserializer.deserialize_map(DocumentVisitor(PhantomData))
+    }
+}
+
+struct DocumentVisitor<T, M, L, E>(PhantomData<(T, M, L, E)>);
+
+impl<'de, T, M, L, E> Visitor<'de> for DocumentVisitor<T, M, L, E>
+where T: de::Deserialize<'de>, M: de::Deserialize<'de>, L: de::Deserialize<'de>, E: Envelope {
+    type Value = Document<T, M, L, E>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a map with the key `{}`", E::DATA)
+    }
+
+    fn visit_map<A>(self, mut map: A) -> ::std::result::Result<Self::Value, A::Error>
+    where A: MapAccess<'de> {
+        let (mut data, mut meta, mut links) = (None, None, None);
+
+        while let Some(key) = try!(map.next_key::<String>()) {
+            if key == E::DATA {
+                data = Some(try!(map.next_value()));
+            } else if key == E::META {
+                meta = Some(try!(map.next_value()));
+            } else if key == E::LINKS {
+                links = Some(try!(map.next_value()));
+            } else {
+                try!(map.next_value::<IgnoredAny>());
+            }
+        }
+
+        let data = try!(data.ok_or_else(|| de::Error::missing_field(E::DATA)));
+
+        Ok(Document {
+            data: data,
+            meta: meta,
+            links: links,
+            _envelope: PhantomData,
+        })
+    }
+}
+
+#[cfg(feature = "serde_json")]
+#[test]
+fn unwrap_envelopes() {
+    use std::collections::BTreeMap;
+    use serialize::json;
+
+    let body = br#"{"links": {"next": "/users?page=2"}, "data": ["a", "b"], "meta": 7}"#;
+
+    let users: Vec<String> = Unwrap::new(json::Deserializer).deserialize(&mut &body[..]).unwrap();
+    assert_eq!(users, ["a", "b"]);
+
+    let doc: Document<Vec<String>, u32, BTreeMap<String, String>> =
+        json::Deserializer.deserialize(&mut &body[..]).unwrap();
+    assert_eq!(doc.meta, Some(7));
+    assert_eq!(doc.links.unwrap()["next"], "/users?page=2");
+
+    let hal = br#"{"_embedded": {"users": []}, "_links": {}}"#;
+    let users: BTreeMap<String, Vec<String>> =
+        Unwrap::<_, Hal>::with_envelope(json::Deserializer).deserialize(&mut &hal[..]).unwrap();
+    assert!(users["users"].is_empty());
+
+    assert!(Unwrap::new(json::Deserializer).deserialize::<u32, _>(&mut &b"7"[..]).is_err());
+}
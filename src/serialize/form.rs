@@ -0,0 +1,629 @@
+//! Serialization of `application/x-www-form-urlencoded` request and response bodies.
+//!
+//! The top-level value must be a struct or map. Nested structs and maps are flattened using
+//! brackets, e.g. `user[name]=Jane`; sequences are written according to `Sequences`.
+//! `None` and `()` values are skipped.
+//!
+//! ```rust
+//! # extern crate anterofit;
+//! # #[macro_use] extern crate serde_derive;
+//! # fn main() {
+//! use anterofit::serialize::Serializer;
+//! use anterofit::serialize::form::{self, Sequences};
+//!
+//! #[derive(Serialize)]
+//! struct Client {
+//!     id: &'static str,
+//! }
+//!
+//! #[derive(Serialize)]
+//! struct TokenRequest {
+//!     grant_type: &'static str,
+//!     scope: Vec<&'static str>,
+//!     client: Client,
+//!     refresh_token: Option<String>,
+//! }
+//!
+//! let request = TokenRequest {
+//!     grant_type: "client_credentials",
+//!     scope: vec!["read", "write"],
+//!     client: Client { id: "my-app" },
+//!     refresh_token: None,
+//! };
+//!
+//! let mut body = Vec::new();
+//! form::Serializer::new().sequences(Sequences::Brackets).serialize(&request, &mut body).unwrap();
+//!
+//! assert_eq!(
+//!     String::from_utf8(body).unwrap(),
+//!     "grant_type=client_credentials&scope%5B%5D=read&scope%5B%5D=write&client%5Bid%5D=my-app"
+//! );
+//! # }
+//! ```
+
+use mime::{self, Mime};
+
+use serde::de::{self, IntoDeserializer, Visitor};
+use serde::de::value::{MapDeserializer, SeqDeserializer};
+use serde::ser;
+
+use url::form_urlencoded::{self, Serializer as FormUrlEncoded};
+
+use std::fmt::Display;
+use std::io::{Read, Write};
+
+use super::{Serialize, Deserialize};
+use super::key::MapKey;
+
+use serialize;
+use ::{Error, Result};
+
+/// How sequences are written as form fields.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Sequences {
+    /// Repeat the key for each element: `tag=a&tag=b`
+    #[default]
+    Repeat,
+    /// Append empty brackets to the key for each element: `tag[]=a&tag[]=b`
+    Brackets,
+    /// Append the index of each element to the key: `tag[0]=a&tag[1]=b`
+    Indexed,
+    /// Join the elements with commas under one key: `tag=a,b`
+    ///
+    /// Elements should be primitives. Not understood by `Deserializer`, which will
+    /// deserialize `a,b` as one element.
+    Comma,
+}
+
+/// Serializer for `application/x-www-form-urlencoded` request bodies.
+#[derive(Clone, Debug, Default)]
+pub struct Serializer {
+    sequences: Sequences,
+}
+
+impl Serializer {
+    /// Create a serializer which repeats keys for sequences.
+    pub fn new() -> Self {
+        Serializer::default()
+    }
+
+    /// Set how sequences are written.
+    pub fn sequences(self, sequences: Sequences) -> Self {
+        Serializer { sequences: sequences }
+    }
+}
+
+impl serialize::Serializer for Serializer {
+    fn serialize<T: Serialize, W: Write>(&self, val: &T, write: &mut W) -> Result<()> {
+        let pairs = try!(to_pairs(val, self.sequences));
+
+        let encoded = FormUrlEncoded::new(String::new()).extend_pairs(pairs).finish();
+
+        write.write_all(encoded.as_bytes()).map_err(Into::into)
+    }
+
+    /// Returns `application/x-www-form-urlencoded`.
+    fn content_type(&self) -> Option<Mime> {
+        Some(mime::form_urlencoded())
+    }
+}
+
+/// Deserializer for `application/x-www-form-urlencoded` response bodies.
+///
+/// Understands the conventions of `Serializer`: repeated keys, bracketed keys and indexed keys
+/// are collected into sequences, and nested bracketed keys into structs or maps.
+/// Values are parsed from strings as needed by the target type.
+#[derive(Clone, Debug, Default)]
+pub struct Deserializer;
+
+impl serialize::Deserializer for Deserializer {
+    fn deserialize<T: Deserialize, R: Read>(&self, read: &mut R) -> Result<T> {
+        let mut bytes = Vec::new();
+        try!(read.read_to_end(&mut bytes));
+
+        let mut root = Value::Map(Vec::new());
+
+        for (key, val) in form_urlencoded::parse(&bytes) {
+            root.insert(&key_path(&key), val.into_owned());
+        }
+
+        T::deserialize(root)
+    }
+}
+
+/// Flatten `val` into a series of form field names and values.
+///
+/// `val` must serialize as a struct or map.
+pub fn to_pairs<T: Serialize + ?Sized>(val: &T, sequences: Sequences) -> Result<Vec<(String, String)>> {
+    let mut pairs = Vec::new();
+
+    try!(val.serialize(Flatten {
+        key: String::new(),
+        pairs: &mut pairs,
+        sequences: sequences,
+    }));
+
+    Ok(pairs)
+}
+
+fn nested_key(key: &str, field: &str) -> String {
+    if key.is_empty() {
+        field.to_owned()
+    } else {
+        format!("{}[{}]", key, field)
+    }
+}
+
+fn unsupported(what: &str) -> Error {
+    ser::Error::custom(format_args!("cannot serialize {} as form fields", what))
+}
+
+/// Serializes a value into the pairs under `key`.
+struct Flatten<'a> {
+    key: String,
+    pairs: &'a mut Vec<(String, String)>,
+    sequences: Sequences,
+}
+
+impl<'a> Flatten<'a> {
+    fn push<V: Display>(self, val: V) -> Result<()> {
+        if self.key.is_empty() {
+            return Err(unsupported("a primitive at the top level"));
+        }
+
+        self.pairs.push((self.key, val.to_string()));
+        Ok(())
+    }
+
+    fn compound(self, key: String) -> FlattenCompound<'a> {
+        let start = self.pairs.len();
+
+        FlattenCompound {
+            key: key,
+            pairs: self.pairs,
+            sequences: self.sequences,
+            index: 0,
+            start: start,
+            map_key: None,
+        }
+    }
+
+    fn seq(self) -> Result<FlattenCompound<'a>> {
+        if self.key.is_empty() {
+            return Err(unsupported("a sequence at the top level"));
+        }
+
+        let key = self.key.clone();
+        Ok(self.compound(key))
+    }
+}
+
+macro_rules! flatten_display {
+    ($($method:ident($ty:ty)),+) => (
+        $(
+            fn $method(self, val: $ty) -> Result<()> {
+                self.push(val)
+            }
+        )+
+    )
+}
+
+impl<'a> ser::Serializer for Flatten<'a> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = FlattenCompound<'a>;
+    type SerializeTuple = FlattenCompound<'a>;
+    type SerializeTupleStruct = FlattenCompound<'a>;
+    type SerializeTupleVariant = FlattenCompound<'a>;
+    type SerializeMap = FlattenCompound<'a>;
+    type SerializeStruct = FlattenCompound<'a>;
+    type SerializeStructVariant = FlattenCompound<'a>;
+
+    flatten_display! {
+        serialize_bool(bool), serialize_char(char), serialize_str(&str),
+        serialize_i8(i8), serialize_i16(i16), serialize_i32(i32), serialize_i64(i64),
+        serialize_u8(u8), serialize_u16(u16), serialize_u32(u32), serialize_u64(u64),
+        serialize_f32(f32), serialize_f64(f64)
+    }
+
+    fn serialize_bytes(self, _val: &[u8]) -> Result<()> {
+        Err(unsupported("bytes"))
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, val: &T) -> Result<()> {
+        val.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<()> {
+        self.push(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, val: &T) -> Result<()> {
+        val.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(self, _name: &'static str, _index: u32,
+                                                        variant: &'static str, val: &T) -> Result<()> {
+        val.serialize(Flatten {
+            key: nested_key(&self.key, variant),
+            pairs: self.pairs,
+            sequences: self.sequences,
+        })
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<FlattenCompound<'a>> {
+        self.seq()
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<FlattenCompound<'a>> {
+        self.seq()
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<FlattenCompound<'a>> {
+        self.seq()
+    }
+
+    fn serialize_tuple_variant(self, _name: &'static str, _index: u32, variant: &'static str,
+                               _len: usize) -> Result<FlattenCompound<'a>> {
+        let key = nested_key(&self.key, variant);
+        Ok(self.compound(key))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<FlattenCompound<'a>> {
+        let key = self.key.clone();
+        Ok(self.compound(key))
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<FlattenCompound<'a>> {
+        let key = self.key.clone();
+        Ok(self.compound(key))
+    }
+
+    fn serialize_struct_variant(self, _name: &'static str, _index: u32, variant: &'static str,
+                                _len: usize) -> Result<FlattenCompound<'a>> {
+        let key = nested_key(&self.key, variant);
+        Ok(self.compound(key))
+    }
+}
+
+/// Serializes the elements of a sequence or the entries of a struct or map under `key`.
+struct FlattenCompound<'a> {
+    key: String,
+    pairs: &'a mut Vec<(String, String)>,
+    sequences: Sequences,
+    index: usize,
+    // The number of pairs before the compound was started
+    start: usize,
+    map_key: Option<String>,
+}
+
+impl<'a> FlattenCompound<'a> {
+    fn element<T: Serialize + ?Sized>(&mut self, val: &T) -> Result<()> {
+        let key = match self.sequences {
+            Sequences::Repeat | Sequences::Comma => self.key.clone(),
+            Sequences::Brackets => format!("{}[]", self.key),
+            Sequences::Indexed => format!("{}[{}]", self.key, self.index),
+        };
+
+        self.index += 1;
+
+        self.field(key, val)
+    }
+
+    fn end_seq(self) -> Result<()> {
+        if self.sequences != Sequences::Comma {
+            return Ok(());
+        }
+
+        let (mut joined, mut rest) = (Vec::new(), Vec::new());
+
+        for (key, val) in self.pairs.drain(self.start..) {
+            if key == self.key {
+                joined.push(val);
+            } else {
+                rest.push((key, val));
+            }
+        }
+
+        if !joined.is_empty() {
+            self.pairs.push((self.key, joined.join(",")));
+        }
+
+        self.pairs.extend(rest);
+
+        Ok(())
+    }
+
+    fn field<T: Serialize + ?Sized>(&mut self, key: String, val: &T) -> Result<()> {
+        val.serialize(Flatten {
+            key: key,
+            pairs: self.pairs,
+            sequences: self.sequences,
+        })
+    }
+}
+
+macro_rules! flatten_seq {
+    ($($trait_:ident :: $method:ident),+) => (
+        $(
+            impl<'a> ser::$trait_ for FlattenCompound<'a> {
+                type Ok = ();
+                type Error = Error;
+
+                fn $method<T: Serialize + ?Sized>(&mut self, val: &T) -> Result<()> {
+                    self.element(val)
+                }
+
+                fn end(self) -> Result<()> {
+                    self.end_seq()
+                }
+            }
+        )+
+    )
+}
+
+flatten_seq! {
+    SerializeSeq::serialize_element, SerializeTuple::serialize_element,
+    SerializeTupleStruct::serialize_field, SerializeTupleVariant::serialize_field
+}
+
+macro_rules! flatten_struct {
+    ($($trait_:ident),+) => (
+        $(
+            impl<'a> ser::$trait_ for FlattenCompound<'a> {
+                type Ok = ();
+                type Error = Error;
+
+                fn serialize_field<T: Serialize + ?Sized>(&mut self, field: &'static str, val: &T) -> Result<()> {
+                    let key = nested_key(&self.key, field);
+                    self.field(key, val)
+                }
+
+                fn end(self) -> Result<()> {
+                    Ok(())
+                }
+            }
+        )+
+    )
+}
+
+flatten_struct! { SerializeStruct, SerializeStructVariant }
+
+impl<'a> ser::SerializeMap for FlattenCompound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        self.map_key = Some(try!(key.serialize(MapKey)));
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, val: &T) -> Result<()> {
+        let map_key = self.map_key.take().expect("`serialize_value()` called before `serialize_key()`");
+        let key = nested_key(&self.key, &map_key);
+        self.field(key, val)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Split `user[address][]` into `["user", "address", ""]`.
+///
+/// Keys with unbalanced brackets are not split.
+fn key_path(key: &str) -> Vec<&str> {
+    let (name, mut rest) = match key.find('[') {
+        Some(idx) if idx > 0 => (&key[..idx], &key[idx..]),
+        _ => return vec![key],
+    };
+
+    let mut path = vec![name];
+
+    while !rest.is_empty() {
+        match (rest.starts_with('['), rest.find(']')) {
+            (true, Some(end)) => {
+                path.push(&rest[1..end]);
+                rest = &rest[end + 1..];
+            },
+            _ => return vec![key],
+        }
+    }
+
+    path
+}
+
+/// The parsed structure of a form body.
+#[derive(Debug, PartialEq)]
+enum Value {
+    Str(String),
+    Seq(Vec<Value>),
+    Map(Vec<(String, Value)>),
+}
+
+impl Value {
+    fn new(path: &[&str], val: String) -> Value {
+        match path.split_first() {
+            None => Value::Str(val),
+            Some((&"", rest)) => Value::Seq(vec![Value::new(rest, val)]),
+            Some((name, rest)) => Value::Map(vec![(name.to_string(), Value::new(rest, val))]),
+        }
+    }
+
+    /// Insert `val` at `path`; values which conflict with existing ones are dropped.
+    fn insert(&mut self, path: &[&str], val: String) {
+        match (path.split_first(), self) {
+            // Repeated key
+            (None, &mut Value::Seq(ref mut elems)) => elems.push(Value::Str(val)),
+            (None, this @ &mut Value::Str(_)) => this.push_seq(Value::Str(val)),
+            (Some((&"", rest)), &mut Value::Seq(ref mut elems)) => {
+                // `a[][x]=1&a[][y]=2` is one element, `a[][x]=1&a[][x]=2` is two
+                if let Some((&next, _)) = rest.split_first() {
+                    if let Some(&mut Value::Map(ref mut entries)) = elems.last_mut() {
+                        if !next.is_empty() && !entries.iter().any(|&(ref name, _)| name == next) {
+                            entries.push((next.to_owned(), Value::new(&rest[1..], val)));
+                            return;
+                        }
+                    }
+                }
+
+                elems.push(Value::new(rest, val));
+            },
+            (Some((&"", rest)), this @ &mut Value::Str(_)) => this.push_seq(Value::new(rest, val)),
+            (Some((name, rest)), &mut Value::Map(ref mut entries)) => {
+                if let Some(&mut (_, ref mut child)) = entries.iter_mut().find(|&&mut (ref key, _)| key == name) {
+                    return child.insert(rest, val);
+                }
+
+                entries.push((name.to_string(), Value::new(rest, val)));
+            },
+            _ => (),
+        }
+    }
+
+    fn push_seq(&mut self, val: Value) {
+        let prev = ::std::mem::replace(self, Value::Seq(Vec::new()));
+
+        if let Value::Seq(ref mut elems) = *self {
+            elems.push(prev);
+            elems.push(val);
+        }
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Value {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! deserialize_parse {
+    ($($method:ident => $visit:ident),+) => (
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+                match self {
+                    Value::Str(val) => visitor.$visit(try!(val.parse().map_err(<Error as de::Error>::custom))),
+                    other => other.deserialize_any(visitor),
+                }
+            }
+        )+
+    )
+}
+
+impl<'de> de::Deserializer<'de> for Value {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self {
+            Value::Str(val) => visitor.visit_string(val),
+            Value::Seq(elems) => visitor.visit_seq(SeqDeserializer::new(elems.into_iter())),
+            Value::Map(entries) => visitor.visit_map(MapDeserializer::new(entries.into_iter())),
+        }
+    }
+
+    deserialize_parse! {
+        deserialize_bool => visit_bool, deserialize_char => visit_char,
+        deserialize_i8 => visit_i8, deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32, deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8, deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32, deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32, deserialize_f64 => visit_f64
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    /// A single value is a sequence of one; indexed keys are taken in order.
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let elems = match self {
+            Value::Seq(elems) => elems,
+            Value::Map(entries) => entries.into_iter().map(|(_, val)| val).collect(),
+            val => vec![val],
+        };
+
+        visitor.visit_seq(SeqDeserializer::new(elems.into_iter()))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(self, _name: &'static str, _len: usize, visitor: V)
+        -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    /// Only unit variants are supported.
+    fn deserialize_enum<V: Visitor<'de>>(self, _name: &'static str, _variants: &'static [&'static str],
+                                         visitor: V) -> Result<V::Value> {
+        match self {
+            Value::Str(val) => visitor.visit_enum(val.into_deserializer()),
+            other => other.deserialize_any(visitor),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        str string bytes byte_buf unit_struct map struct identifier ignored_any
+    }
+}
+
+#[test]
+fn form_round_trip() {
+    use std::collections::BTreeMap;
+    use serialize::{Serializer as Ser, Deserializer as De};
+
+    let mut user = BTreeMap::new();
+    user.insert("name", vec!["Jane"]);
+    user.insert("tags", vec!["a", "b"]);
+
+    let mut form = BTreeMap::new();
+    form.insert("user", user);
+
+    for &(sequences, expected) in &[
+        (Sequences::Repeat, "user[name]=Jane&user[tags]=a&user[tags]=b"),
+        (Sequences::Brackets, "user[name][]=Jane&user[tags][]=a&user[tags][]=b"),
+        (Sequences::Indexed, "user[name][0]=Jane&user[tags][0]=a&user[tags][1]=b"),
+    ] {
+        let pairs = to_pairs(&form, sequences).unwrap();
+        let joined: Vec<_> = pairs.iter().map(|&(ref k, ref v)| format!("{}={}", k, v)).collect();
+        assert_eq!(joined.join("&"), expected);
+
+        let mut body = Vec::new();
+        Serializer::new().sequences(sequences).serialize(&form, &mut body).unwrap();
+
+        let parsed: BTreeMap<String, BTreeMap<String, Vec<String>>> = Deserializer.deserialize(&mut &body[..]).unwrap();
+        assert_eq!(parsed["user"]["tags"], ["a", "b"]);
+        assert_eq!(parsed["user"]["name"], ["Jane"]);
+    }
+
+    let parsed: BTreeMap<String, (u32, bool)> = Deserializer.deserialize(&mut &b"pair=1&pair=true"[..]).unwrap();
+    assert_eq!(parsed["pair"], (1, true));
+
+    let pairs = to_pairs(&form, Sequences::Comma).unwrap();
+    assert_eq!(pairs, [("user[name]".to_string(), "Jane".to_string()), ("user[tags]".to_string(), "a,b".to_string())]);
+
+    assert!(to_pairs(&42, Sequences::Repeat).is_err());
+}
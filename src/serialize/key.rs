@@ -0,0 +1,84 @@
+//! Serialization of map keys, for formats which only allow string keys.
+
+use serde::ser::{self, Impossible};
+
+use super::Serialize;
+
+use ::{Error, Result};
+
+fn compound_key() -> Error {
+    ser::Error::custom("map keys must be strings, numbers, `bool` or `char`")
+}
+
+/// Serializes map keys to strings.
+pub struct MapKey;
+
+macro_rules! map_key_display {
+    ($($method:ident($ty:ty)),+) => (
+        $(
+            fn $method(self, val: $ty) -> Result<String> {
+                Ok(val.to_string())
+            }
+        )+
+    )
+}
+
+macro_rules! map_key_unsupported {
+    ($($method:ident($($arg:ty),*) -> $ret:ty),+) => (
+        $(
+            fn $method(self, $(_: $arg),*) -> Result<$ret> {
+                Err(compound_key())
+            }
+        )+
+    )
+}
+
+impl ser::Serializer for MapKey {
+    type Ok = String;
+    type Error = Error;
+    type SerializeSeq = Impossible<String, Error>;
+    type SerializeTuple = Impossible<String, Error>;
+    type SerializeTupleStruct = Impossible<String, Error>;
+    type SerializeTupleVariant = Impossible<String, Error>;
+    type SerializeMap = Impossible<String, Error>;
+    type SerializeStruct = Impossible<String, Error>;
+    type SerializeStructVariant = Impossible<String, Error>;
+
+    map_key_display! {
+        serialize_bool(bool), serialize_char(char), serialize_str(&str),
+        serialize_i8(i8), serialize_i16(i16), serialize_i32(i32), serialize_i64(i64),
+        serialize_u8(u8), serialize_u16(u16), serialize_u32(u32), serialize_u64(u64),
+        serialize_f32(f32), serialize_f64(f64)
+    }
+
+    map_key_unsupported! {
+        serialize_bytes(&[u8]) -> String,
+        serialize_none() -> String,
+        serialize_unit() -> String,
+        serialize_unit_struct(&'static str) -> String,
+        serialize_seq(Option<usize>) -> Self::SerializeSeq,
+        serialize_tuple(usize) -> Self::SerializeTuple,
+        serialize_tuple_struct(&'static str, usize) -> Self::SerializeTupleStruct,
+        serialize_tuple_variant(&'static str, u32, &'static str, usize) -> Self::SerializeTupleVariant,
+        serialize_map(Option<usize>) -> Self::SerializeMap,
+        serialize_struct(&'static str, usize) -> Self::SerializeStruct,
+        serialize_struct_variant(&'static str, u32, &'static str, usize) -> Self::SerializeStructVariant
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, val: &T) -> Result<String> {
+        val.serialize(self)
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<String> {
+        Ok(variant.to_owned())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, val: &T) -> Result<String> {
+        val.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(self, _name: &'static str, _index: u32,
+                                                        _variant: &'static str, _val: &T) -> Result<String> {
+        Err(compound_key())
+    }
+}
@@ -6,17 +6,42 @@
 
 use mime::Mime;
 
+use net::response::Response;
+
 use std::fmt;
 use std::io::{Read, Write};
 
 pub mod none;
 
+pub mod form;
+
+mod key;
+
+pub mod negotiate;
+
+pub mod envelope;
+
 #[cfg(feature = "serde_json")]
 pub mod json;
 
-#[cfg(feature = "serde_xml")]
+#[cfg(feature = "serde-xml-rs")]
 pub mod xml;
 
+#[cfg(feature = "rmp-serde")]
+pub mod msgpack;
+
+#[cfg(feature = "serde_cbor")]
+pub mod cbor;
+
+#[cfg(feature = "prost")]
+pub mod protobuf;
+
+#[cfg(feature = "serde_yaml")]
+pub mod yaml;
+
+#[cfg(feature = "toml")]
+pub mod toml;
+
 pub use serde::Serialize;
 pub use serde::de::DeserializeOwned as Deserialize;
 use serde::de::IntoDeserializer;
@@ -39,9 +64,86 @@ pub trait Serializer: Send + Sync + 'static {
 pub trait Deserializer: Send + Sync + 'static {
     /// Deserialize `T` from `read`, returning the result.
     fn deserialize<T: Deserialize, R: Read>(&self, read: &mut R) -> ::Result<T>;
+
+    /// Deserialize `T` from the body of `response`, returning the result.
+    ///
+    /// Override this if the response headers, such as `Content-Type`, are needed
+    /// for deserialization. By default, this calls `deserialize()` with the response body.
+    fn deserialize_response<T: Deserialize>(&self, response: &mut Response) -> ::Result<T> {
+        self.deserialize(response)
+    }
+
+    /// Return the MIME types of the responses this deserializer can handle, if applicable.
+    ///
+    /// Used to set the `Accept` header of the request this deserializer is being used for,
+    /// unless the request already has one. By default, returns an empty vector and no `Accept`
+    /// header is set.
+    fn accept(&self) -> Vec<Mime> {
+        Vec::new()
+    }
+}
+
+/// Placeholder for the serializer and deserializer of a `RequestBuilder` which selects those of
+/// the adapter.
+///
+/// Replaced by calling `RequestBuilder::serializer()` or `RequestBuilder::deserializer()`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FromAdapter;
+
+/// Selects the serializer used for a request: either the adapter's serializer, `S`,
+/// or an override set on the `RequestBuilder`.
+pub trait ChooseSerializer<S>: Send + Sync + 'static {
+    /// The type of the chosen serializer.
+    type Serializer: Serializer;
+
+    /// Choose between `self` and the adapter's serializer.
+    fn choose<'a>(&'a self, adapter: &'a S) -> &'a Self::Serializer;
+}
+
+impl<S: Serializer> ChooseSerializer<S> for FromAdapter {
+    type Serializer = S;
+
+    fn choose<'a>(&'a self, adapter: &'a S) -> &'a S {
+        adapter
+    }
+}
+
+impl<S, S_: Serializer> ChooseSerializer<S> for S_ {
+    type Serializer = S_;
+
+    fn choose<'a>(&'a self, _adapter: &'a S) -> &'a S_ {
+        self
+    }
+}
+
+/// Selects the deserializer used for a request: either the adapter's deserializer, `D`,
+/// or an override set on the `RequestBuilder`.
+pub trait ChooseDeserializer<D>: Send + Sync + 'static {
+    /// The type of the chosen deserializer.
+    type Deserializer: Deserializer;
+
+    /// Choose between `self` and the adapter's deserializer.
+    fn choose<'a>(&'a self, adapter: &'a D) -> &'a Self::Deserializer;
+}
+
+impl<D: Deserializer> ChooseDeserializer<D> for FromAdapter {
+    type Deserializer = D;
+
+    fn choose<'a>(&'a self, adapter: &'a D) -> &'a D {
+        adapter
+    }
+}
+
+impl<D, D_: Deserializer> ChooseDeserializer<D> for D_ {
+    type Deserializer = D_;
+
+    fn choose<'a>(&'a self, _adapter: &'a D) -> &'a D_ {
+        self
+    }
 }
 
 /// A deserializer which attempts to parse values from the response as a string.
+#[derive(Clone)]
 pub struct FromStrDeserializer;
 
 /// A simple series of key-value pairs that can be serialized as a map.
@@ -126,11 +228,18 @@ impl Error for ::Error {
     }
 }
 
+impl ::serde::ser::Error for ::Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        let error: Box<StdError + Send + Sync> = msg.to_string().into();
+        ::Error::Serialize(error)
+    }
+}
+
 impl Deserializer for FromStrDeserializer {
     fn deserialize<T: Deserialize, R: Read>(&self, read: &mut R) -> ::Result<T> {
 
         let mut string = String::new();
-        let string = read.read_to_string(&mut string)?;
+        read.read_to_string(&mut string)?;
         T::deserialize(string.into_deserializer())
     }
 }
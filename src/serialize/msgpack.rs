@@ -0,0 +1,74 @@
+//! Integration with the `rmp-serde` crate providing MessagePack serialization.
+
+extern crate rmp_serde;
+
+use mime::{self, Mime};
+
+use std::io::{Read, Write};
+
+use super::{Serialize, Deserialize};
+
+use serialize;
+use ::{Error, Result};
+
+/// Serializer for MessagePack request bodies which writes structs as maps with field names.
+#[derive(Clone, Debug, Default)]
+pub struct Serializer;
+
+impl serialize::Serializer for Serializer {
+    fn serialize<T: Serialize, W: Write>(&self, val: &T, write: &mut W) -> Result<()> {
+        Error::map_serialize(self::rmp_serde::encode::write_named(write, val))
+    }
+
+    /// Returns `application/msgpack`.
+    fn content_type(&self) -> Option<Mime> {
+        Some(mime::msgpack())
+    }
+}
+
+/// Serializer for MessagePack request bodies which writes structs as arrays, without
+/// field names.
+///
+/// This is more compact, but the server must expect fields in the same order.
+#[derive(Clone, Debug, Default)]
+pub struct CompactSerializer;
+
+impl serialize::Serializer for CompactSerializer {
+    fn serialize<T: Serialize, W: Write>(&self, val: &T, write: &mut W) -> Result<()> {
+        Error::map_serialize(self::rmp_serde::encode::write(write, val))
+    }
+
+    fn content_type(&self) -> Option<Mime> {
+        Some(mime::msgpack())
+    }
+}
+
+/// Deserializer for pulling values from MessagePack response bodies.
+///
+/// Structs may be written either as maps or as arrays.
+#[derive(Clone, Debug, Default)]
+pub struct Deserializer;
+
+impl serialize::Deserializer for Deserializer {
+    fn deserialize<T: Deserialize, R: Read>(&self, read: &mut R) -> Result<T> {
+        Error::map_deserialize(self::rmp_serde::decode::from_read(read))
+    }
+}
+
+#[test]
+fn msgpack_round_trip() {
+    use std::collections::BTreeMap;
+    use serialize::{Serializer as Ser, Deserializer as De};
+
+    let mut map = BTreeMap::new();
+    map.insert("answer".to_string(), vec![4, 2]);
+
+    let (mut named, mut compact) = (Vec::new(), Vec::new());
+    Serializer.serialize(&map, &mut named).unwrap();
+    CompactSerializer.serialize(&map, &mut compact).unwrap();
+
+    for buf in &[named, compact] {
+        let parsed: BTreeMap<String, Vec<u8>> = Deserializer.deserialize(&mut &buf[..]).unwrap();
+        assert_eq!(parsed, map);
+    }
+}
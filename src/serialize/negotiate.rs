@@ -0,0 +1,262 @@
+//! Deserialization of responses based on their `Content-Type`.
+//!
+//! ```rust
+//! # extern crate anterofit;
+//! # fn main() {
+//! use anterofit::Adapter;
+//! use anterofit::serialize::negotiate::Negotiate;
+//!
+//! // Sends `Accept: application/json, text/plain` and deserializes responses accordingly.
+//! let adapter = Adapter::builder()
+//!     .deserializer(Negotiate::new().json().text())
+//!     .build();
+//! # }
+//! ```
+
+use hyper::header::ContentType;
+
+use mime::{self, Mime};
+
+use std::io::Read;
+
+use net::response::Response;
+
+use super::{Deserializer, Deserialize, FromStrDeserializer};
+
+use ::{Error, Result};
+
+/// A deserializer which chooses from a set of registered deserializers by the `Content-Type`
+/// of the response.
+///
+/// The `Accept` header of requests is set to the registered MIME types, in the order they
+/// were registered (unless the request already has an `Accept` header).
+///
+/// Parameters such as `charset` are ignored when matching `Content-Type`.
+///
+/// If the response has no `Content-Type`, or no deserializer is registered for it,
+/// deserialization fails with `NegotiateError`. `deserialize()`, which has no response to
+/// inspect, uses the first registered deserializer (the first type in `Accept`).
+#[derive(Clone, Debug, Default)]
+pub struct Negotiate<F = ()> {
+    formats: F,
+}
+
+impl Negotiate {
+    /// Create a new instance with no formats registered.
+    pub fn new() -> Self {
+        Negotiate { formats: () }
+    }
+}
+
+impl<F: Formats> Negotiate<F> {
+    /// Register `deserializer` for responses with the given content type.
+    pub fn format<D>(self, content_type: Mime, deserializer: D) -> Negotiate<Format<D, F>>
+    where D: Deserializer {
+        Negotiate {
+            formats: Format {
+                content_type: content_type,
+                deserializer: deserializer,
+                next: self.formats,
+            }
+        }
+    }
+
+    /// Register `json::Deserializer` for `application/json`.
+    #[cfg(feature = "serde_json")]
+    pub fn json(self) -> Negotiate<Format<super::json::Deserializer, F>> {
+        self.format(mime::json(), super::json::Deserializer)
+    }
+
+    /// Register `xml::Deserializer` for `application/xml`.
+    #[cfg(feature = "serde-xml-rs")]
+    pub fn xml(self) -> Negotiate<Format<super::xml::Deserializer, F>> {
+        self.format(mime!(Application/Xml), super::xml::Deserializer)
+    }
+
+    /// Register `msgpack::Deserializer` for `application/msgpack`.
+    #[cfg(feature = "rmp-serde")]
+    pub fn msgpack(self) -> Negotiate<Format<super::msgpack::Deserializer, F>> {
+        self.format(mime::msgpack(), super::msgpack::Deserializer)
+    }
+
+    /// Register `cbor::Deserializer` for `application/cbor`.
+    #[cfg(feature = "serde_cbor")]
+    pub fn cbor(self) -> Negotiate<Format<super::cbor::Deserializer, F>> {
+        self.format(mime::cbor(), super::cbor::Deserializer)
+    }
+
+    /// Register `yaml::Deserializer` for `application/yaml`.
+    #[cfg(feature = "serde_yaml")]
+    pub fn yaml(self) -> Negotiate<Format<super::yaml::Deserializer, F>> {
+        self.format(mime::yaml(), super::yaml::Deserializer)
+    }
+
+    /// Register `toml::Deserializer` for `application/toml`.
+    #[cfg(feature = "toml")]
+    pub fn toml(self) -> Negotiate<Format<super::toml::Deserializer, F>> {
+        self.format(mime::toml(), super::toml::Deserializer)
+    }
+
+    /// Register `form::Deserializer` for `application/x-www-form-urlencoded`.
+    pub fn form(self) -> Negotiate<Format<super::form::Deserializer, F>> {
+        self.format(mime::form_urlencoded(), super::form::Deserializer)
+    }
+
+    /// Register `FromStrDeserializer` for `text/plain`.
+    pub fn text(self) -> Negotiate<Format<FromStrDeserializer, F>> {
+        self.format(mime!(Text/Plain), FromStrDeserializer)
+    }
+}
+
+impl<F: Formats> Deserializer for Negotiate<F> {
+    /// Deserialize with the first registered deserializer.
+    ///
+    /// Returns `NegotiateError::NoContentType` if none are registered.
+    fn deserialize<T: Deserialize, R: Read>(&self, read: &mut R) -> Result<T> {
+        self.formats.deserialize_first(read)
+            .unwrap_or_else(|| Err(Error::deserialize(NegotiateError::NoContentType)))
+    }
+
+    fn deserialize_response<T: Deserialize>(&self, response: &mut Response) -> Result<T> {
+        let content_type = match response.headers.get::<ContentType>() {
+            Some(&ContentType(ref content_type)) => content_type.clone(),
+            None => return Err(Error::deserialize(NegotiateError::NoContentType)),
+        };
+
+        self.formats.deserialize_response(&content_type, response)
+            .unwrap_or_else(|| Err(Error::deserialize(NegotiateError::Unsupported(content_type))))
+    }
+
+    fn accept(&self) -> Vec<Mime> {
+        let mut accept = Vec::new();
+        self.formats.accept(&mut accept);
+        accept
+    }
+}
+
+/// A set of deserializers registered with `Negotiate`.
+pub trait Formats: Send + Sync + 'static {
+    /// Deserialize `T` from `response` with the deserializer registered for `content_type`,
+    /// or return `None` if there is none.
+    fn deserialize_response<T: Deserialize>(&self, content_type: &Mime, response: &mut Response) -> Option<Result<T>>;
+
+    /// Deserialize `T` from `read` with the first registered deserializer,
+    /// or return `None` if there is none.
+    fn deserialize_first<T: Deserialize, R: Read>(&self, read: &mut R) -> Option<Result<T>>;
+
+    /// Add the registered MIME types to `accept`.
+    fn accept(&self, accept: &mut Vec<Mime>);
+}
+
+impl Formats for () {
+    fn deserialize_response<T: Deserialize>(&self, _: &Mime, _: &mut Response) -> Option<Result<T>> {
+        None
+    }
+
+    fn deserialize_first<T: Deserialize, R: Read>(&self, _: &mut R) -> Option<Result<T>> {
+        None
+    }
+
+    fn accept(&self, _: &mut Vec<Mime>) {}
+}
+
+/// A deserializer registered for a content type, followed by the rest of the set.
+#[derive(Clone, Debug)]
+pub struct Format<D, F> {
+    content_type: Mime,
+    deserializer: D,
+    next: F,
+}
+
+impl<D: Deserializer, F: Formats> Formats for Format<D, F> {
+    fn deserialize_response<T: Deserialize>(&self, content_type: &Mime, response: &mut Response) -> Option<Result<T>> {
+        // Check earlier registrations first
+        if let Some(res) = self.next.deserialize_response(content_type, response) {
+            return Some(res);
+        }
+
+        if essence_eq(&self.content_type, content_type) {
+            Some(self.deserializer.deserialize_response(response))
+        } else {
+            None
+        }
+    }
+
+    fn deserialize_first<T: Deserialize, R: Read>(&self, read: &mut R) -> Option<Result<T>> {
+        if let Some(res) = self.next.deserialize_first(read) {
+            return Some(res);
+        }
+
+        Some(self.deserializer.deserialize(read))
+    }
+
+    fn accept(&self, accept: &mut Vec<Mime>) {
+        self.next.accept(accept);
+        accept.push(self.content_type.clone());
+    }
+}
+
+/// Compare MIME types, ignoring parameters.
+fn essence_eq(left: &Mime, right: &Mime) -> bool {
+    left.0 == right.0 && left.1 == right.1
+}
+
+quick_error! {
+    /// Error returned by `Negotiate` when a response cannot be deserialized.
+    #[derive(Debug)]
+    pub enum NegotiateError {
+        /// "The response has no `Content-Type` header"
+        NoContentType {
+            description("The response has no `Content-Type` header")
+        }
+        /// "No deserializer was registered for the response's `Content-Type`"
+        Unsupported(content_type: Mime) {
+            description("No deserializer was registered for the response's `Content-Type`")
+            display("No deserializer was registered for the response's `Content-Type`: {}", content_type)
+        }
+    }
+}
+
+#[test]
+fn negotiate_formats() {
+    use net::response::mock_response;
+
+    let negotiate = Negotiate::new().format(mime::json(), FromStrDeserializer).text();
+
+    assert_eq!(negotiate.accept(), vec![mime::json(), mime!(Text/Plain)]);
+
+    let mut response = mock_response("HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\n\
+                                      Content-Length: 5\r\n\r\nHello");
+    let res: Result<String> = negotiate.deserialize_response(&mut response);
+    assert_eq!(res.unwrap(), "Hello");
+
+    let res: Option<Result<String>> = negotiate.formats.deserialize_response(&mime::octet_stream(), &mut response);
+    assert!(res.is_none());
+
+    // Registered deserializers see the whole response
+    #[derive(Clone)]
+    struct StatusDeserializer;
+
+    impl Deserializer for StatusDeserializer {
+        fn deserialize<T: Deserialize, R: Read>(&self, _read: &mut R) -> Result<T> {
+            Err(Error::deserialize(NegotiateError::NoContentType))
+        }
+
+        fn deserialize_response<T: Deserialize>(&self, response: &mut Response) -> Result<T> {
+            FromStrDeserializer.deserialize(&mut response.status.to_string().as_bytes())
+        }
+    }
+
+    let mut response = mock_response("HTTP/1.1 201 Created\r\nContent-Type: application/json\r\n\
+                                      Content-Length: 0\r\n\r\n");
+    let res: Result<String> = Negotiate::new().format(mime::json(), StatusDeserializer).deserialize_response(&mut response);
+    assert_eq!(res.unwrap(), "201 Created");
+
+    // Without a response, the first registered format is used
+    let negotiate = Negotiate::new().text().form();
+    let res: Result<String> = negotiate.deserialize(&mut &b"Hello"[..]);
+    assert_eq!(res.unwrap(), "Hello");
+
+    let res: Result<String> = Negotiate::new().deserialize(&mut &b"Hello"[..]);
+    assert!(res.is_err());
+}
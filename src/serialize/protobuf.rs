@@ -0,0 +1,102 @@
+//! Integration with the `prost` crate providing Protocol Buffers request and response bodies.
+//!
+//! Protobuf messages are not `Serialize`, so instead of using the adapter's serializer, wrap them
+//! in `Proto` as the request body or service method return type:
+//!
+//! ```rust
+//! # #[macro_use] extern crate anterofit;
+//! # extern crate prost;
+//! # fn main() {}
+//! use anterofit::serialize::protobuf::Proto;
+//!
+//! #[derive(Clone, PartialEq, prost::Message)]
+//! pub struct GetUser {
+//!     #[prost(uint64, tag = "1")]
+//!     pub id: u64,
+//! }
+//!
+//! #[derive(Clone, PartialEq, prost::Message)]
+//! pub struct User {
+//!     #[prost(string, tag = "1")]
+//!     pub name: String,
+//! }
+//!
+//! service! {
+//!     pub trait UserService {
+//!         fn get_user(&self, id: u64) -> Proto<User> {
+//!             POST("/rpc/GetUser");
+//!             headers! { "Accept" => "application/x-protobuf" };
+//!             body!(Proto(GetUser { id: id }))
+//!         }
+//!     }
+//! }
+//! ```
+
+use prost::Message;
+
+use std::io::{Cursor, Read};
+use std::ops::{Deref, DerefMut};
+
+use mime;
+
+use net::body::{EagerBody, Readable, ReadableResult};
+use net::response::{FromResponse, Response};
+
+use serialize::{Serializer, Deserializer};
+
+use ::{Error, Result};
+
+/// Wrapper for a Protobuf message, encoded as a request body with the `Content-Type`
+/// `application/x-protobuf`, or decoded from a response body.
+///
+/// The adapter's serializer and deserializer are not used.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Proto<T>(pub T);
+
+impl<T> Deref for Proto<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Proto<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T: Message> EagerBody for Proto<T> {
+    type Readable = Cursor<Vec<u8>>;
+
+    fn into_readable<S>(self, _ser: &S) -> ReadableResult<Self::Readable> where S: Serializer {
+        Ok(Readable::from_cursor(Cursor::new(self.0.encode_to_vec()), mime::protobuf()))
+    }
+}
+
+impl<T: Message + Default + 'static> FromResponse for Proto<T> {
+    fn from_response<D>(_des: &D, mut response: Response) -> Result<Self>
+        where D: Deserializer {
+        let mut buf = Vec::new();
+        try!(response.read_to_end(&mut buf));
+
+        Error::map_deserialize(T::decode(&buf[..])).map(Proto)
+    }
+}
+
+#[test]
+fn proto_body() {
+    use serialize::none::NoSerializer;
+
+    #[derive(Clone, PartialEq, Message)]
+    struct Ping {
+        #[prost(uint32, tag = "1")]
+        seq: u32,
+    }
+
+    let readable = Proto(Ping { seq: 150 }).into_readable(&NoSerializer).unwrap();
+
+    assert_eq!(readable.content_type, Some(mime::protobuf()));
+    assert_eq!(readable.readable.into_inner(), [0x08, 0x96, 0x01]);
+}
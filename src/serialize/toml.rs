@@ -0,0 +1,63 @@
+//! Integration with the `toml` crate providing TOML serialization.
+//!
+//! ##Note
+//! The top-level value must be a struct or map. When serializing, fields which are
+//! structs or maps (TOML tables) must come after all other fields.
+
+extern crate toml;
+
+use mime::{self, Mime};
+
+use std::io::{Read, Write};
+
+use super::{Serialize, Deserialize};
+
+use serialize;
+use ::{Error, Result};
+
+/// Serializer for TOML request bodies.
+#[derive(Clone, Debug, Default)]
+pub struct Serializer;
+
+impl serialize::Serializer for Serializer {
+    fn serialize<T: Serialize, W: Write>(&self, val: &T, write: &mut W) -> Result<()> {
+        let toml = try!(Error::map_serialize(self::toml::to_string(val)));
+        write.write_all(toml.as_bytes()).map_err(Into::into)
+    }
+
+    /// Returns `application/toml`.
+    fn content_type(&self) -> Option<Mime> {
+        Some(mime::toml())
+    }
+}
+
+/// Deserializer for pulling values from TOML response bodies.
+#[derive(Clone, Debug, Default)]
+pub struct Deserializer;
+
+impl serialize::Deserializer for Deserializer {
+    fn deserialize<T: Deserialize, R: Read>(&self, read: &mut R) -> Result<T> {
+        let mut toml = String::new();
+        try!(read.read_to_string(&mut toml));
+        Error::map_deserialize(self::toml::from_str(&toml))
+    }
+}
+
+#[test]
+fn toml_round_trip() {
+    use std::collections::BTreeMap;
+    use serialize::{Serializer as Ser, Deserializer as De};
+
+    let mut table = BTreeMap::new();
+    table.insert("replicas".to_string(), 3);
+
+    let mut config = BTreeMap::new();
+    config.insert("deployment".to_string(), table);
+
+    let mut buf = Vec::new();
+    Serializer.serialize(&config, &mut buf).unwrap();
+    assert_eq!(String::from_utf8(buf.clone()).unwrap(), "[deployment]\nreplicas = 3\n");
+
+    let parsed: BTreeMap<String, BTreeMap<String, u32>> = Deserializer.deserialize(&mut &buf[..]).unwrap();
+    assert_eq!(parsed, config);
+}
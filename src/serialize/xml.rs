@@ -1,17 +1,89 @@
-//! Integration with the `serde_xml` crate providing XML serialization.
+//! Integration with the `serde-xml-rs` crate providing XML serialization.
 //!
-//! ##Note
-//! As of November 2016, only deserialization is supported by `serde_xml`.
+//! Deserialization is provided by `serde-xml-rs`; serialization is implemented here.
+//!
+//! ```rust
+//! # extern crate anterofit;
+//! # #[macro_use] extern crate serde_derive;
+//! # fn main() {
+//! use anterofit::serialize::Serializer;
+//! use anterofit::serialize::xml;
+//!
+//! #[derive(Serialize)]
+//! struct Order {
+//!     id: u64,
+//!     items: Vec<&'static str>,
+//!     note: Option<&'static str>,
+//! }
+//!
+//! let order = Order { id: 7, items: vec!["fish", "chips"], note: Some("no vinegar & salt") };
+//!
+//! let mut body = Vec::new();
+//! xml::Serializer::new().root("order").serialize(&order, &mut body).unwrap();
+//!
+//! assert_eq!(
+//!     String::from_utf8(body).unwrap(),
+//!     "<order><id>7</id><items>fish</items><items>chips</items>\
+//!      <note>no vinegar &amp; salt</note></order>"
+//! );
+//! # }
+//! ```
+
+extern crate serde_xml_rs as serde_xml;
+
+use mime::Mime;
 
-extern crate serde_xml;
+use serde::ser;
 
-use std::io::Read;
+use std::borrow::Cow;
+use std::fmt::Display;
+use std::io::{Read, Write};
 
-use super::Deserialize;
+use super::{Serialize, Deserialize};
+use super::key::MapKey;
 
 use serialize;
 use ::{Error, Result};
 
+/// Serializer for XML request bodies.
+///
+/// Structs and maps become elements containing an element for each field or entry,
+/// sequences become repeated elements with the same name, and `None` fields are omitted.
+/// Text is escaped; element names are not checked.
+///
+/// The root element is named after the serialized type if it is a struct, or `root` otherwise,
+/// unless a name is set with `root()`. The elements of a sequence at the root are named `item`.
+#[derive(Clone, Debug, Default)]
+pub struct Serializer {
+    root: Option<Cow<'static, str>>,
+}
+
+impl Serializer {
+    /// Create a serializer which names the root element after the serialized type.
+    pub fn new() -> Self {
+        Serializer::default()
+    }
+
+    /// Set the name of the root element.
+    pub fn root<R: Into<Cow<'static, str>>>(self, root: R) -> Self {
+        Serializer { root: Some(root.into()) }
+    }
+}
+
+impl serialize::Serializer for Serializer {
+    fn serialize<T: Serialize, W: Write>(&self, val: &T, write: &mut W) -> Result<()> {
+        val.serialize(Element {
+            writer: write,
+            tag: self.root.as_ref().map(|root| root.to_string()),
+            root: true,
+        })
+    }
+
+    /// Returns `application/xml`.
+    fn content_type(&self) -> Option<Mime> {
+        Some(mime!(Application/Xml))
+    }
+}
 
 /// Deserializer for pulling values from XML responses.
 #[derive(Clone, Debug, Default)]
@@ -19,6 +91,306 @@ pub struct Deserializer;
 
 impl serialize::Deserializer for Deserializer {
     fn deserialize<T: Deserialize, R: Read>(&self, read: &mut R) -> Result<T> {
-        Error::map_deserialize(self::serde_xml::de::from_iter(read.bytes()))
+        Error::map_deserialize(self::serde_xml::deserialize(read))
+    }
+}
+
+fn write_escaped<W: Write + ?Sized>(writer: &mut W, text: &str) -> Result<()> {
+    let bytes = text.as_bytes();
+    let mut last = 0;
+
+    for (idx, &byte) in bytes.iter().enumerate() {
+        let escaped = match byte {
+            b'&' => "&amp;",
+            b'<' => "&lt;",
+            b'>' => "&gt;",
+            _ => continue,
+        };
+
+        try!(writer.write_all(&bytes[last..idx]));
+        try!(writer.write_all(escaped.as_bytes()));
+        last = idx + 1;
+    }
+
+    try!(writer.write_all(&bytes[last..]));
+    Ok(())
+}
+
+/// Serializes a value as an element named `tag`.
+struct Element<'a, W: ?Sized + 'a> {
+    writer: &'a mut W,
+    // `None` at the root if no name was configured
+    tag: Option<String>,
+    root: bool,
+}
+
+impl<'a, W: Write + ?Sized + 'a> Element<'a, W> {
+    fn tag(&self, default: &str) -> String {
+        self.tag.clone().unwrap_or_else(|| default.to_owned())
     }
-}
\ No newline at end of file
+
+    fn text<V: Display>(self, val: V) -> Result<()> {
+        let tag = self.tag("root");
+        try!(write!(self.writer, "<{}>", tag));
+        try!(write_escaped(self.writer, &val.to_string()));
+        try!(write!(self.writer, "</{}>", tag));
+        Ok(())
+    }
+
+    fn empty(self, default: &str) -> Result<()> {
+        let tag = self.tag(default);
+        try!(write!(self.writer, "<{}/>", tag));
+        Ok(())
+    }
+
+    fn open(self, tags: Vec<String>) -> Result<Children<'a, W>> {
+        for tag in &tags {
+            try!(write!(self.writer, "<{}>", tag));
+        }
+
+        Ok(Children {
+            writer: self.writer,
+            item_tag: String::new(),
+            close: tags,
+            map_key: None,
+        })
+    }
+
+    fn seq(self) -> Result<Children<'a, W>> {
+        if self.root {
+            let tag = self.tag("root");
+            let mut children = try!(self.open(vec![tag]));
+            children.item_tag = "item".to_owned();
+            return Ok(children);
+        }
+
+        // Elements are repeated in place of this one
+        let item_tag = self.tag("item");
+        let mut children = try!(self.open(vec![]));
+        children.item_tag = item_tag;
+        Ok(children)
+    }
+}
+
+macro_rules! element_text {
+    ($($method:ident($ty:ty)),+) => (
+        $(
+            fn $method(self, val: $ty) -> Result<()> {
+                self.text(val)
+            }
+        )+
+    )
+}
+
+impl<'a, W: Write + ?Sized + 'a> ser::Serializer for Element<'a, W> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Children<'a, W>;
+    type SerializeTuple = Children<'a, W>;
+    type SerializeTupleStruct = Children<'a, W>;
+    type SerializeTupleVariant = Children<'a, W>;
+    type SerializeMap = Children<'a, W>;
+    type SerializeStruct = Children<'a, W>;
+    type SerializeStructVariant = Children<'a, W>;
+
+    element_text! {
+        serialize_bool(bool), serialize_char(char), serialize_str(&str),
+        serialize_i8(i8), serialize_i16(i16), serialize_i32(i32), serialize_i64(i64),
+        serialize_u8(u8), serialize_u16(u16), serialize_u32(u32), serialize_u64(u64),
+        serialize_f32(f32), serialize_f64(f64)
+    }
+
+    fn serialize_bytes(self, _val: &[u8]) -> Result<()> {
+        Err(ser::Error::custom("cannot serialize bytes as XML"))
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, val: &T) -> Result<()> {
+        val.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        self.empty("root")
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<()> {
+        self.empty(name)
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<()> {
+        self.text(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, name: &'static str, val: &T) -> Result<()> {
+        let tag = self.tag(name);
+
+        val.serialize(Element {
+            writer: self.writer,
+            tag: Some(tag),
+            root: self.root,
+        })
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(self, name: &'static str, _index: u32,
+                                                        variant: &'static str, val: &T) -> Result<()> {
+        let tag = self.tag(name);
+        let mut children = try!(self.open(vec![tag]));
+        try!(children.child(variant.to_owned(), val));
+        children.end()
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Children<'a, W>> {
+        self.seq()
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Children<'a, W>> {
+        self.seq()
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Children<'a, W>> {
+        self.seq()
+    }
+
+    fn serialize_tuple_variant(self, name: &'static str, _index: u32, variant: &'static str,
+                               _len: usize) -> Result<Children<'a, W>> {
+        let tag = self.tag(name);
+        let mut children = try!(self.open(vec![tag]));
+        children.item_tag = variant.to_owned();
+        Ok(children)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Children<'a, W>> {
+        let tag = self.tag("root");
+        self.open(vec![tag])
+    }
+
+    fn serialize_struct(self, name: &'static str, _len: usize) -> Result<Children<'a, W>> {
+        let tag = self.tag(name);
+        self.open(vec![tag])
+    }
+
+    fn serialize_struct_variant(self, name: &'static str, _index: u32, variant: &'static str,
+                                _len: usize) -> Result<Children<'a, W>> {
+        let tag = self.tag(name);
+        self.open(vec![tag, variant.to_owned()])
+    }
+}
+
+/// Serializes the elements of a sequence or the fields of a struct or map.
+struct Children<'a, W: ?Sized + 'a> {
+    writer: &'a mut W,
+    item_tag: String,
+    // Tags to close at the end, outermost first
+    close: Vec<String>,
+    map_key: Option<String>,
+}
+
+impl<'a, W: Write + ?Sized + 'a> Children<'a, W> {
+    fn child<T: Serialize + ?Sized>(&mut self, tag: String, val: &T) -> Result<()> {
+        val.serialize(Element {
+            writer: &mut *self.writer,
+            tag: Some(tag),
+            root: false,
+        })
+    }
+
+    fn item<T: Serialize + ?Sized>(&mut self, val: &T) -> Result<()> {
+        let tag = self.item_tag.clone();
+        self.child(tag, val)
+    }
+
+    fn end(self) -> Result<()> {
+        for tag in self.close.iter().rev() {
+            try!(write!(self.writer, "</{}>", tag));
+        }
+
+        Ok(())
+    }
+}
+
+macro_rules! children_seq {
+    ($($trait_:ident :: $method:ident),+) => (
+        $(
+            impl<'a, W: Write + ?Sized + 'a> ser::$trait_ for Children<'a, W> {
+                type Ok = ();
+                type Error = Error;
+
+                fn $method<T: Serialize + ?Sized>(&mut self, val: &T) -> Result<()> {
+                    self.item(val)
+                }
+
+                fn end(self) -> Result<()> {
+                    Children::end(self)
+                }
+            }
+        )+
+    )
+}
+
+children_seq! {
+    SerializeSeq::serialize_element, SerializeTuple::serialize_element,
+    SerializeTupleStruct::serialize_field, SerializeTupleVariant::serialize_field
+}
+
+macro_rules! children_struct {
+    ($($trait_:ident),+) => (
+        $(
+            impl<'a, W: Write + ?Sized + 'a> ser::$trait_ for Children<'a, W> {
+                type Ok = ();
+                type Error = Error;
+
+                fn serialize_field<T: Serialize + ?Sized>(&mut self, field: &'static str, val: &T) -> Result<()> {
+                    self.child(field.to_owned(), val)
+                }
+
+                fn end(self) -> Result<()> {
+                    Children::end(self)
+                }
+            }
+        )+
+    )
+}
+
+children_struct! { SerializeStruct, SerializeStructVariant }
+
+impl<'a, W: Write + ?Sized + 'a> ser::SerializeMap for Children<'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        self.map_key = Some(try!(key.serialize(MapKey)));
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, val: &T) -> Result<()> {
+        let key = self.map_key.take().expect("`serialize_value()` called before `serialize_key()`");
+        self.child(key, val)
+    }
+
+    fn end(self) -> Result<()> {
+        Children::end(self)
+    }
+}
+
+#[test]
+fn xml_serializer() {
+    use std::collections::BTreeMap;
+    use serialize::Serializer as Ser;
+
+    fn to_string<T: Serialize>(ser: &Serializer, val: &T) -> String {
+        let mut out = Vec::new();
+        ser.serialize(val, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    let mut map = BTreeMap::new();
+    map.insert("a", vec![Some("<1>"), None, Some("2")]);
+    map.insert("b", vec![]);
+
+    assert_eq!(to_string(&Serializer::new(), &map), "<root><a>&lt;1&gt;</a><a>2</a></root>");
+    assert_eq!(to_string(&Serializer::new().root("list"), &vec![1, 2]), "<list><item>1</item><item>2</item></list>");
+    assert_eq!(to_string(&Serializer::new(), &"text"), "<root>text</root>");
+}
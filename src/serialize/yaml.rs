@@ -0,0 +1,53 @@
+//! Integration with the `serde_yaml` crate providing YAML serialization.
+
+extern crate serde_yaml;
+
+use mime::{self, Mime};
+
+use std::io::{Read, Write};
+
+use super::{Serialize, Deserialize};
+
+use serialize;
+use ::{Error, Result};
+
+/// Serializer for YAML request bodies.
+#[derive(Clone, Debug, Default)]
+pub struct Serializer;
+
+impl serialize::Serializer for Serializer {
+    fn serialize<T: Serialize, W: Write>(&self, val: &T, write: &mut W) -> Result<()> {
+        Error::map_serialize(self::serde_yaml::to_writer(write, val))
+    }
+
+    /// Returns `application/yaml`.
+    fn content_type(&self) -> Option<Mime> {
+        Some(mime::yaml())
+    }
+}
+
+/// Deserializer for pulling values from YAML response bodies.
+#[derive(Clone, Debug, Default)]
+pub struct Deserializer;
+
+impl serialize::Deserializer for Deserializer {
+    fn deserialize<T: Deserialize, R: Read>(&self, read: &mut R) -> Result<T> {
+        Error::map_deserialize(self::serde_yaml::from_reader(read))
+    }
+}
+
+#[test]
+fn yaml_round_trip() {
+    use std::collections::BTreeMap;
+    use serialize::{Serializer as Ser, Deserializer as De};
+
+    let mut map = BTreeMap::new();
+    map.insert("answer".to_string(), vec![4, 2]);
+
+    let mut buf = Vec::new();
+    Serializer.serialize(&map, &mut buf).unwrap();
+    assert!(String::from_utf8(buf.clone()).unwrap().contains("answer:\n"));
+
+    let parsed: BTreeMap<String, Vec<u8>> = Deserializer.deserialize(&mut &buf[..]).unwrap();
+    assert_eq!(parsed, map);
+}